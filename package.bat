@@ -1,3 +1,5 @@
 cargo fmt
-jmake make
-jmake info -o package.toml -u https://github.com/DukeofStars/voran/releases/download/${version}/${name}.jellyfish
\ No newline at end of file
+cargo build --release
+mkdir dist\bin
+copy target\release\voran.exe dist\bin\
+voran pack dist --name voran --friendly-name Voran --version %1 --url https://github.com/DukeofStars/voran/releases/download/{version}/{name}.jellyfish
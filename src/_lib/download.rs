@@ -1,80 +1,369 @@
 use std::{
-    cmp::min,
-    fs::File,
-    io::Write,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+use failure::Fail;
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::Client;
+use reqwest::{header::RANGE, Client, StatusCode};
+use sha2::{Digest, Sha256};
+
+/// Errors returned when a downloaded file can't be trusted.
+#[derive(Debug)]
+pub enum DownloadError {
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DownloadError::ChecksumMismatch {
+                url,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Checksum mismatch for '{}': expected sha256 {}, got {}",
+                url, expected, actual
+            ),
+        }
+    }
+}
+
+impl Fail for DownloadError {}
+
+/// Number of times a failed download is retried before giving up.
+pub const DOWNLOAD_RETRIES: u32 = 5;
+/// Delay before the first retry, doubled for every following one.
+pub const DOWNLOAD_BACKOFF: Duration = Duration::from_millis(500);
 
 /// Download file using a reqwest::Client, from url and put the contents at path.
 ///
+/// The file is downloaded to `<path>.part` first. Dropped connections are retried with exponential
+/// backoff, resuming with a HTTP Range request where the server supports it, and a `.part` file
+/// left behind by an earlier run is resumed the same way. The url a `.part` file was downloaded from
+/// is kept next to it, and a `.part` file from any other url is discarded rather than resumed.
+///
+/// If sha256 is set, the file is hashed while it downloads and removed again if it doesn't match.
+///
 /// ```rust_async
 /// let path = "google_index.html";
 /// let url = "https://google.com/index.html";
-/// voran::download_file(&reqwest::Client::new(), url, path).expect("Failed to download file");
+/// voran::download_file(&reqwest::Client::new(), url, path, None).expect("Failed to download file");
 /// ```
 pub async fn download_file<P: AsRef<Path>>(
     client: &Client,
     url: &str,
     path: P,
+    sha256: Option<&str>,
 ) -> Result<PathBuf, failure::Error> {
+    let part = part_path(path.as_ref());
+    let source = source_path(&part);
+    // An older version or another mirror would be spliced onto the wrong file
+    if part.exists() && fs::read_to_string(&source).ok().as_deref() != Some(url) {
+        fs::remove_file(&part)?;
+    }
+    fs::write(&source, url)?;
+
+    let mut attempt = 0;
+    let digest = loop {
+        match download_part(client, url, &part).await {
+            Ok(digest) => break digest,
+            Err(Attempt::Retry(err)) if attempt < DOWNLOAD_RETRIES => {
+                let delay = DOWNLOAD_BACKOFF * 2u32.pow(attempt);
+                attempt += 1;
+                println!(
+                    "{}, retrying in {:.1}s ({}/{})",
+                    err,
+                    delay.as_secs_f32(),
+                    attempt,
+                    DOWNLOAD_RETRIES
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(Attempt::Retry(err)) | Err(Attempt::Fail(err)) => return Err(err),
+        }
+    };
+
+    if let Some(expected) = sha256 {
+        if !digest.eq_ignore_ascii_case(expected) {
+            fs::remove_file(&part)?;
+            fs::remove_file(&source)?;
+            return Err(DownloadError::ChecksumMismatch {
+                url: url.to_string(),
+                expected: expected.to_string(),
+                actual: digest,
+            }
+            .into());
+        }
+    }
+
+    fs::rename(&part, &path)?;
+    fs::remove_file(&source)?;
+    Ok(path.as_ref().to_path_buf())
+}
+
+/// A file downloaded from one of several mirrors.
+#[derive(Debug)]
+pub struct Downloaded {
+    pub path: PathBuf,
+    /// The mirror the file was downloaded from.
+    pub url: String,
+}
+
+/// Download a file like download_file, trying each url in order until one succeeds.
+///
+/// Mirrors that fail or serve a file with the wrong checksum are skipped.
+pub async fn download_mirrors<P: AsRef<Path>>(
+    client: &Client,
+    urls: &[String],
+    path: P,
+    sha256: Option<&str>,
+) -> Result<Downloaded, failure::Error> {
+    let mut errors = vec![];
+    for url in urls {
+        match download_file(client, url, &path, sha256).await {
+            Ok(path) => {
+                return Ok(Downloaded {
+                    path,
+                    url: url.clone(),
+                })
+            }
+            Err(err) => {
+                println!("Mirror '{}' failed: {}", url, err);
+                errors.push(err.to_string());
+            }
+        }
+    }
+    Err(failure::err_msg(format!(
+        "Every mirror failed: {}",
+        errors.join(", ")
+    )))
+}
+
+/// Path partially downloaded files are kept at.
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Path the url of a partially downloaded file is kept at.
+fn source_path(part: &Path) -> PathBuf {
+    let mut name = part.file_name().unwrap_or_default().to_os_string();
+    name.push(".source");
+    part.with_file_name(name)
+}
+
+/// Outcome of a failed download attempt.
+enum Attempt {
+    /// The connection failed and may work if tried again.
+    Retry(failure::Error),
+    /// Trying again won't help.
+    Fail(failure::Error),
+}
+
+/// Download url into part, continuing from the end of part if it exists.
+/// Returns the hex encoded sha256 of the whole file.
+async fn download_part(client: &Client, url: &str, part: &Path) -> Result<String, Attempt> {
+    let offset = fs::metadata(part).map(|x| x.len()).unwrap_or(0);
+
     // Reqwest setup
-    let res = client
-        .get(url)
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let res = request
         .send()
         .await
-        .or(Err(failure::err_msg(format!(
-            "Failed to GET from '{}'",
+        .map_err(|_| Attempt::Retry(failure::err_msg(format!("Failed to GET from '{}'", url))))?;
+
+    let status = res.status();
+    if status == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The part file is no use, start again from zero.
+        fs::remove_file(part).map_err(|err| Attempt::Fail(err.into()))?;
+        return Err(Attempt::Retry(failure::err_msg(format!(
+            "Failed to resume download from '{}'",
             url
-        ))))?;
-    let total_size = res.content_length().ok_or(failure::err_msg(format!(
-        "Failed to get content length from '{}'",
-        &url
-    )))?;
-
-    // Indicatif setup
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})").unwrap()
-        .progress_chars("#>-"));
+        ))));
+    }
+    if !status.is_success() {
+        let err = failure::err_msg(format!("Failed to GET from '{}': {}", url, status));
+        return Err(if status.is_server_error() {
+            Attempt::Retry(err)
+        } else {
+            Attempt::Fail(err)
+        });
+    }
+
+    // Servers that ignore the Range header send the whole file again.
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    let mut hasher = Sha256::new();
+    let mut file = if resumed {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(part)
+            .map_err(|err| Attempt::Fail(err.into()))?;
+        io::copy(&mut file, &mut hasher).map_err(|err| Attempt::Fail(err.into()))?;
+        file
+    } else {
+        File::create(part).map_err(|_| {
+            Attempt::Fail(failure::err_msg(format!(
+                "Failed to create file '{}'",
+                part.display()
+            )))
+        })?
+    };
+    let mut downloaded: u64 = if resumed { offset } else { 0 };
+
+    // Indicatif setup, with a spinner if the server doesn't say how big the file is.
+    let pb = match res.content_length() {
+        Some(len) => {
+            let pb = ProgressBar::new(downloaded + len);
+            pb.set_style(ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})").unwrap()
+                .progress_chars("#>-"));
+            pb
+        }
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec})")
+                    .unwrap(),
+            );
+            pb
+        }
+    };
+    pb.set_position(downloaded);
 
     // download chunks
-    let mut file = File::create(&path).or(Err(failure::err_msg(format!(
-        "Failed to create file '{}'",
-        path.as_ref().to_str().unwrap()
-    ))))?;
-    let mut downloaded: u64 = 0;
     let mut stream = res.bytes_stream();
-
     while let Some(item) = stream.next().await {
-        let chunk = item.or(Err(failure::err_msg(format!(
-            "Error while downloading file"
-        ))))?;
-        file.write_all(&chunk).or(Err(failure::err_msg(format!(
-            "Error while writing to file"
-        ))))?;
-        let new = min(downloaded + (chunk.len() as u64), total_size);
-        downloaded = new;
-        pb.set_position(new);
+        let chunk = item.map_err(|_| {
+            pb.abandon();
+            Attempt::Retry(failure::err_msg("Error while downloading file"))
+        })?;
+        file.write_all(&chunk)
+            .map_err(|_| Attempt::Fail(failure::err_msg("Error while writing to file")))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        pb.set_position(downloaded);
     }
 
     pb.finish();
-
-    Ok(path.as_ref().to_path_buf())
+    Ok(hex::encode(hasher.finalize()))
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        thread,
+    };
 
     use reqwest::Client;
     use tokio::fs::{self, OpenOptions};
 
     use crate::download_file;
 
+    /// Local HTTP stand-in: answers each connection with the next canned response
+    /// and records the requests it received.
+    fn serve(responses: Vec<&'static [u8]>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/package.jellyfish",
+            listener.local_addr().unwrap()
+        );
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 4096];
+                let len = stream.read(&mut buf).unwrap();
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&buf[..len]).to_lowercase());
+                stream.write_all(response).unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn dropped_download_is_resumed() {
+        let (url, requests) = serve(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello",
+            b"HTTP/1.1 206 Partial Content\r\nContent-Length: 5\r\nContent-Range: bytes 5-9/10\r\nConnection: close\r\n\r\nworld",
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.jellyfish");
+
+        // sha256 of "helloworld"
+        let sha256 = "936a185caaa266bb9cbe981e9e05cb78cd732b0b3280eb944412bb6f8f8f07af";
+        download_file(&Client::new(), &url, &path, Some(sha256))
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "helloworld");
+        assert!(requests.lock().unwrap()[1].contains("range: bytes=5-"));
+        assert!(!dir.path().join("package.jellyfish.part").exists());
+        assert!(!dir.path().join("package.jellyfish.part.source").exists());
+    }
+
+    #[tokio::test]
+    async fn part_from_another_url_is_discarded() {
+        let (url, requests) = serve(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.jellyfish");
+        let part = dir.path().join("package.jellyfish.part");
+        std::fs::write(&part, "stale").unwrap();
+        std::fs::write(
+            dir.path().join("package.jellyfish.part.source"),
+            "https://example.com/1.0.0/package.jellyfish",
+        )
+        .unwrap();
+
+        download_file(&Client::new(), &url, &path, None)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+        assert!(!requests.lock().unwrap()[0].contains("range:"));
+        assert!(!dir.path().join("package.jellyfish.part.source").exists());
+    }
+
+    #[tokio::test]
+    async fn download_without_content_length() {
+        let (url, _) = serve(vec![
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.jellyfish");
+
+        download_file(&Client::new(), &url, &path, None)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello");
+    }
+
     #[tokio::test]
     #[ignore]
     async fn download_full_file() {
@@ -85,7 +374,9 @@ mod tests {
                 .await
                 .unwrap();
         }
-        download_file(&Client::new(), url, &path).await.unwrap();
+        download_file(&Client::new(), url, &path, None)
+            .await
+            .unwrap();
         let mut options = OpenOptions::default();
         options.read(true);
         let file = options.open(&path).await.unwrap();
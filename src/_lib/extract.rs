@@ -0,0 +1,519 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+use tar::{Archive, EntryType};
+use zip::ZipArchive;
+
+/// Limits applied while extracting a package.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct ExtractLimits {
+    /// Maximum number of bytes extracted from one package.
+    pub max_total_size: u64,
+    /// Maximum number of entries in one package.
+    pub max_entries: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: 8 * 1024 * 1024 * 1024,
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// Errors returned when an archive entry would be unsafe to extract.
+#[derive(Debug)]
+pub enum ExtractError {
+    /// The entry is absolute, contains `..` or is written through a symlink.
+    PathTraversal { entry: String },
+    /// The entry is a link pointing outside the package.
+    UnsafeLink { entry: String, target: String },
+    /// The entry is a device file, fifo or other special file.
+    UnsupportedEntry { entry: String },
+    /// Extracting the entry would exceed the maximum total size.
+    TooLarge { entry: String, limit: u64 },
+    /// The archive has more entries than allowed.
+    TooManyEntries { entry: String, limit: u64 },
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::PathTraversal { entry } => {
+                write!(f, "'{}' would be extracted outside the package", entry)
+            }
+            ExtractError::UnsafeLink { entry, target } => write!(
+                f,
+                "'{}' links to '{}', which is outside the package",
+                entry, target
+            ),
+            ExtractError::UnsupportedEntry { entry } => {
+                write!(f, "'{}' is a special file, which is not allowed", entry)
+            }
+            ExtractError::TooLarge { entry, limit } => write!(
+                f,
+                "'{}' exceeds the maximum extracted size of {} bytes",
+                entry, limit
+            ),
+            ExtractError::TooManyEntries { entry, limit } => {
+                write!(f, "'{}' exceeds the maximum of {} entries", entry, limit)
+            }
+        }
+    }
+}
+
+impl Fail for ExtractError {}
+
+/// Keeps count of what has been extracted so far.
+struct Budget {
+    limits: ExtractLimits,
+    size: u64,
+    entries: u64,
+}
+
+impl Budget {
+    fn new(limits: ExtractLimits) -> Self {
+        Self {
+            limits,
+            size: 0,
+            entries: 0,
+        }
+    }
+
+    fn entry(&mut self, entry: &str) -> Result<(), ExtractError> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(ExtractError::TooManyEntries {
+                entry: entry.to_string(),
+                limit: self.limits.max_entries,
+            });
+        }
+        Ok(())
+    }
+
+    fn size(&mut self, entry: &str, size: u64) -> Result<(), ExtractError> {
+        self.size = self.size.saturating_add(size);
+        if self.size > self.limits.max_total_size {
+            return Err(ExtractError::TooLarge {
+                entry: entry.to_string(),
+                limit: self.limits.max_total_size,
+            });
+        }
+        Ok(())
+    }
+
+    fn remaining(&self) -> u64 {
+        self.limits.max_total_size.saturating_sub(self.size)
+    }
+}
+
+/// Extract a tar archive into out, checking every entry against the package root first.
+pub fn unpack_tar<R: Read>(
+    reader: R,
+    out: &Path,
+    limits: ExtractLimits,
+) -> Result<(), failure::Error> {
+    fs::create_dir_all(out)?;
+    let mut budget = Budget::new(limits);
+    let mut archive = Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        let name = path.display().to_string();
+        budget.entry(&name)?;
+        let relative = checked_path(out, &path, &name)?;
+
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous | EntryType::GNUSparse => {
+                budget.size(&name, entry.size())?;
+            }
+            EntryType::Directory => {}
+            EntryType::Symlink => {
+                let target = entry.link_name()?.unwrap_or_default().to_path_buf();
+                let base = relative.parent().unwrap_or(Path::new(""));
+                check_link(out, base, &target, &name)?;
+            }
+            // Hard link targets are relative to the package root
+            EntryType::Link => {
+                let target = entry.link_name()?.unwrap_or_default().to_path_buf();
+                check_link(out, Path::new(""), &target, &name)?;
+            }
+            // Metadata, already applied to the following entry
+            EntryType::XGlobalHeader | EntryType::XHeader => continue,
+            _ => return Err(ExtractError::UnsupportedEntry { entry: name }.into()),
+        }
+
+        entry.unpack_in(out)?;
+    }
+    check_extracted_links(out, out)
+}
+
+/// Extract a zip archive into out, checking every entry against the package root first.
+pub fn unpack_zip<R: Read + Seek>(
+    reader: R,
+    out: &Path,
+    limits: ExtractLimits,
+) -> Result<(), failure::Error> {
+    fs::create_dir_all(out)?;
+    let mut budget = Budget::new(limits);
+    let mut archive = ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        budget.entry(&name)?;
+        let relative = checked_path(out, Path::new(&name), &name)?;
+        let dest = out.join(&relative);
+
+        let mode = file.unix_mode();
+        let file_type = mode.map(|mode| mode & 0o170000);
+        if file.is_dir() {
+            fs::create_dir_all(&dest)?;
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Never write through a link planted by an earlier entry of the same name
+        if let Ok(metadata) = dest.symlink_metadata() {
+            if metadata.is_dir() {
+                return Err(ExtractError::PathTraversal { entry: name }.into());
+            }
+            fs::remove_file(&dest)?;
+        }
+        match file_type {
+            // Symlink, stored as a file containing the target
+            Some(0o120000) => {
+                let mut target = String::new();
+                file.by_ref().take(4096).read_to_string(&mut target)?;
+                let base = relative.parent().unwrap_or(Path::new(""));
+                check_link(out, base, Path::new(&target), &name)?;
+                symlink(Path::new(&target), &dest)?;
+            }
+            None | Some(0) | Some(0o100000) => {
+                // Declared sizes can lie, so stop reading once the budget is spent.
+                let mut out_file = File::create(&dest)?;
+                let written = io::copy(
+                    &mut file.by_ref().take(budget.remaining() + 1),
+                    &mut out_file,
+                )?;
+                budget.size(&name, written)?;
+
+                #[cfg(unix)]
+                if let Some(mode) = mode {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&dest, fs::Permissions::from_mode(mode & 0o777))?;
+                }
+            }
+            _ => return Err(ExtractError::UnsupportedEntry { entry: name }.into()),
+        }
+    }
+    check_extracted_links(out, out)
+}
+
+/// Path of an entry relative to the package root.
+/// Fails if it would leave the root or be written through a symlink extracted earlier.
+fn checked_path(out: &Path, path: &Path, name: &str) -> Result<PathBuf, ExtractError> {
+    let traversal = || ExtractError::PathTraversal {
+        entry: name.to_string(),
+    };
+    if path
+        .components()
+        .any(|x| !matches!(x, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(traversal());
+    }
+    let relative = normalize(path).ok_or_else(traversal)?;
+
+    let mut ancestor = out.to_path_buf();
+    if let Some(parent) = relative.parent() {
+        for component in parent.components() {
+            ancestor.push(component);
+            if let Ok(metadata) = ancestor.symlink_metadata() {
+                if metadata.file_type().is_symlink() {
+                    return Err(traversal());
+                }
+            }
+        }
+    }
+    Ok(relative)
+}
+
+/// Check that a link target, relative to base (a folder of the package), points inside the package.
+///
+/// Targets may not pass through a link extracted earlier, as its own target isn't taken into
+/// account here (eg. `up -> ..` followed by `evil -> up/../outside`).
+fn check_link(out: &Path, base: &Path, target: &Path, name: &str) -> Result<(), ExtractError> {
+    let unsafe_link = || ExtractError::UnsafeLink {
+        entry: name.to_string(),
+        target: target.display().to_string(),
+    };
+    let mut resolved = base.to_path_buf();
+    let mut components = target.components().peekable();
+    while let Some(component) = components.next() {
+        match component {
+            Component::Normal(x) => {
+                resolved.push(x);
+                let is_link = out
+                    .join(&resolved)
+                    .symlink_metadata()
+                    .map(|x| x.file_type().is_symlink())
+                    .unwrap_or(false);
+                if is_link && components.peek().is_some() {
+                    return Err(unsafe_link());
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return Err(unsafe_link());
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return Err(unsafe_link()),
+        }
+    }
+    Ok(())
+}
+
+/// Check that every extracted symlink below dir resolves inside out.
+/// A link extracted later can change where an earlier one leads, so this runs once at the end.
+fn check_extracted_links(out: &Path, dir: &Path) -> Result<(), failure::Error> {
+    let root = fs::canonicalize(out)?;
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            // Dangling links don't lead anywhere
+            if let Ok(resolved) = fs::canonicalize(&path) {
+                if !resolved.starts_with(&root) {
+                    return Err(ExtractError::UnsafeLink {
+                        entry: path.strip_prefix(out)?.display().to_string(),
+                        target: fs::read_link(&path)?.display().to_string(),
+                    }
+                    .into());
+                }
+            }
+        } else if file_type.is_dir() {
+            check_extracted_links(out, &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Lexically resolve `.` and `..`, returning None if the path leaves its root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(x) => out.push(x),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_with(entries: &[(&str, EntryType, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, entry_type, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            // Bypass tar's own path checks to build malicious archives.
+            let name = &mut header.as_old_mut().name;
+            name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o644);
+            if *entry_type == EntryType::Symlink {
+                header.set_link_name(contents).unwrap();
+                header.set_size(0);
+                header.set_cksum();
+                builder.append(&header, io::empty()).unwrap();
+            } else {
+                header.set_size(contents.len() as u64);
+                header.set_cksum();
+                builder.append(&header, contents.as_bytes()).unwrap();
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// (path, symlink target or None for a file, contents)
+    fn zip_with(entries: &[(&str, Option<&str>, &str)]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default();
+        for (path, target, contents) in entries {
+            match target {
+                Some(target) => writer.add_symlink(*path, *target, options).unwrap(),
+                None => {
+                    writer.start_file(*path, options).unwrap();
+                    writer.write_all(contents.as_bytes()).unwrap();
+                }
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn unpack_zip_in(archive: Vec<u8>, out: &Path) -> Result<(), ExtractError> {
+        unpack_zip(io::Cursor::new(archive), out, ExtractLimits::default())
+            .map_err(|err| err.downcast::<ExtractError>().unwrap())
+    }
+
+    fn unpack(archive: Vec<u8>, limits: ExtractLimits) -> Result<tempfile::TempDir, ExtractError> {
+        let dir = tempfile::tempdir().unwrap();
+        unpack_tar(&archive[..], dir.path(), limits)
+            .map(|_| dir)
+            .map_err(|err| err.downcast::<ExtractError>().unwrap())
+    }
+
+    #[test]
+    fn safe_archive_is_extracted() {
+        let archive = tar_with(&[
+            ("bin/tool", EntryType::Regular, "hi"),
+            ("bin/alias", EntryType::Symlink, "tool"),
+        ]);
+        let dir = unpack(archive, ExtractLimits::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("bin/alias")).unwrap(),
+            "hi"
+        );
+    }
+
+    #[test]
+    fn unsafe_entries_are_rejected() {
+        let cases = vec![
+            (
+                tar_with(&[("../evil", EntryType::Regular, "hi")]),
+                "'../evil' would be extracted outside the package",
+            ),
+            (
+                tar_with(&[("bin/tool", EntryType::Symlink, "../../etc/passwd")]),
+                "'bin/tool' links to '../../etc/passwd', which is outside the package",
+            ),
+            (
+                tar_with(&[
+                    ("lib", EntryType::Symlink, "."),
+                    ("lib/tool", EntryType::Regular, "hi"),
+                ]),
+                "'lib/tool' would be extracted outside the package",
+            ),
+            (
+                tar_with(&[("dev", EntryType::Char, "")]),
+                "'dev' is a special file, which is not allowed",
+            ),
+            (
+                tar_with(&[
+                    ("sub/up", EntryType::Symlink, ".."),
+                    ("esc", EntryType::Symlink, "sub/up/.."),
+                ]),
+                "'esc' links to 'sub/up/..', which is outside the package",
+            ),
+            (
+                // here doesn't exist yet when esc is checked
+                tar_with(&[
+                    ("esc", EntryType::Symlink, "here/.."),
+                    ("here", EntryType::Symlink, "."),
+                ]),
+                "'esc' links to 'here/..', which is outside the package",
+            ),
+        ];
+        for (archive, message) in cases {
+            let err = unpack(archive, ExtractLimits::default()).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("package");
+        let err = unpack_zip_in(
+            zip_with(&[
+                ("sub/up", Some(".."), ""),
+                ("evil", Some("sub/up/../outside.txt"), ""),
+                ("evil", None, "hi"),
+            ]),
+            &out,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'evil' links to 'sub/up/../outside.txt', which is outside the package"
+        );
+        assert!(!dir.path().join("outside.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zip_entries_replace_earlier_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("package");
+        fs::create_dir_all(&out).unwrap();
+        unpack_zip_in(
+            zip_with(&[("tool", Some("bin"), ""), ("tool", None, "hi")]),
+            &out,
+        )
+        .unwrap();
+        assert!(!out
+            .join("tool")
+            .symlink_metadata()
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(out.join("tool")).unwrap(), "hi");
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let archive = tar_with(&[
+            ("a", EntryType::Regular, "hello"),
+            ("b", EntryType::Regular, "world"),
+        ]);
+        let err = unpack(
+            archive.clone(),
+            ExtractLimits {
+                max_total_size: 8,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'b' exceeds the maximum extracted size of 8 bytes"
+        );
+
+        let err = unpack(
+            archive,
+            ExtractLimits {
+                max_entries: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "'b' exceeds the maximum of 1 entries");
+    }
+}
@@ -0,0 +1,22 @@
+use std::{path::Path, process::Command};
+
+/// Run git in a repository and return its trimmed output.
+///
+/// git-rs doesn't report failures, so anything that has to know whether git succeeded goes
+/// through the git command line instead.
+pub fn run(repo: &Path, args: &[&str]) -> Result<String, failure::Error> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|err| failure::err_msg(format!("Failed to run git: {}", err)))?;
+    if !output.status.success() {
+        return Err(failure::err_msg(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,319 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use futures_util::{stream, StreamExt};
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{manifest::sha256_file, package::is_folder_name, packages::GetPackages, signature};
+
+/// Name of the file listing every package of an index remote.
+pub const INDEX_FILE: &str = "index.toml";
+
+/// File in a synced version folder holding the url its package.toml is fetched from.
+const SOURCE_FILE: &str = ".source";
+/// File in a synced version folder holding the sha256 of its package.toml listed in the index.
+const DIGEST_FILE: &str = ".digest";
+
+/// Number of package.toml files prefetch downloads at once.
+const PREFETCH_CONCURRENCY: usize = 16;
+
+/// Top-level file of an index remote.
+///
+/// The remote serves the same layout as a git repository (`<name>/<version>/package.toml`),
+/// so only the package.toml files that are needed have to be downloaded.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Index {
+    /// Package name -> version folder -> sha256 of its package.toml.
+    /// Clients drop their copy of a package.toml when its digest changes, eg. after a
+    /// `voran publish --force` or when LATEST moves.
+    pub packages: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Index {
+    /// Build the index of a package repository.
+    /// Version folders without a package.toml are left out.
+    pub async fn scan(repo: &Path) -> Result<Self, failure::Error> {
+        let mut packages = BTreeMap::new();
+        for get_package in GetPackages::new(repo).load().await? {
+            let mut versions = BTreeMap::new();
+            for version in get_package.versions() {
+                let path = get_package.dir.join(&version).join("package.toml");
+                if path.exists() {
+                    versions.insert(version, sha256_file(&path)?);
+                }
+            }
+            let name = get_package
+                .dir
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            packages.insert(name, versions);
+        }
+        Ok(Self { packages })
+    }
+}
+
+/// Download the index of a remote and lay out its packages in dir.
+///
+/// Version folders start out empty, their package.toml is fetched the first time it is read.
+pub async fn sync(url: &str, dir: &Path) -> Result<(), failure::Error> {
+    let base = if url.ends_with('/') {
+        url.to_string()
+    } else {
+        format!("{}/", url)
+    };
+    let contents = get(&Client::new(), &format!("{}{}", base, INDEX_FILE))
+        .await?
+        .ok_or_else(|| failure::err_msg(format!("{} has no {}", url, INDEX_FILE)))?;
+    let index: Index = toml::from_slice(&contents)?;
+    for (name, versions) in &index.packages {
+        for x in versions.keys().chain([name]) {
+            if !is_folder_name(x) {
+                return Err(failure::err_msg(format!(
+                    "{} of {} lists an invalid name '{}'",
+                    INDEX_FILE, url, x
+                )));
+            }
+        }
+    }
+
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(INDEX_FILE), &contents)?;
+
+    // Drop packages and versions that were removed from the index
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let versions = match index.packages.get(&name) {
+            Some(versions) => versions,
+            None => {
+                fs::remove_dir_all(entry.path())?;
+                continue;
+            }
+        };
+        for version in entry.path().read_dir()? {
+            let version = version?;
+            let name = version.file_name().to_string_lossy().to_string();
+            if version.file_type()?.is_dir() && !versions.contains_key(&name) {
+                fs::remove_dir_all(version.path())?;
+            }
+        }
+    }
+
+    for (name, versions) in &index.packages {
+        for (version, digest) in versions {
+            let version_dir = dir.join(name).join(version);
+            fs::create_dir_all(&version_dir)?;
+            let digest_file = version_dir.join(DIGEST_FILE);
+            if fs::read_to_string(&digest_file).ok().as_deref() != Some(digest.as_str()) {
+                remove_fetched(&version_dir)?;
+            }
+            fs::write(digest_file, digest)?;
+            fs::write(
+                version_dir.join(SOURCE_FILE),
+                format!("{}{}/{}/", base, name, version),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Download the package.toml (and signature) of a synced version folder if it isn't there yet.
+/// Does nothing for folders that don't come from an index remote.
+pub(crate) fn fetch_missing(dir: &Path) -> Result<(), failure::Error> {
+    if !needs_fetch(dir) {
+        return Ok(());
+    }
+    let dir = dir.to_path_buf();
+    block_on(async move { fetch(&Client::new(), &dir).await })
+}
+
+/// Download the package.toml files of many version folders at once, eg. before listing every
+/// package of a remote. Failures are left for package() to report on the folder it loads.
+pub(crate) async fn prefetch(dirs: &[PathBuf]) {
+    let client = Client::new();
+    stream::iter(dirs.iter().filter(|x| needs_fetch(x)))
+        .map(|dir| fetch(&client, dir))
+        .buffer_unordered(PREFETCH_CONCURRENCY)
+        .for_each(|_| async {})
+        .await;
+}
+
+fn needs_fetch(dir: &Path) -> bool {
+    !dir.join("package.toml").exists() && dir.join(SOURCE_FILE).exists()
+}
+
+async fn fetch(client: &Client, dir: &Path) -> Result<(), failure::Error> {
+    let path = dir.join("package.toml");
+    let url = format!(
+        "{}package.toml",
+        fs::read_to_string(dir.join(SOURCE_FILE))?.trim()
+    );
+    let signature_url = format!("{}.minisig", url);
+    let package = get(client, &url).await?;
+    let signature = get(client, &signature_url).await?;
+
+    // A missing package.toml is reported by the caller like any other missing file.
+    let package = match package {
+        Some(package) => package,
+        None => return Ok(()),
+    };
+    if let Ok(expected) = fs::read_to_string(dir.join(DIGEST_FILE)) {
+        if hex::encode(Sha256::digest(&package)) != expected {
+            return Err(failure::err_msg(format!(
+                "'{}' doesn't match the index, run voran update",
+                url
+            )));
+        }
+    }
+    if let Some(signature) = signature {
+        fs::write(signature::signature_path(&path), signature)?;
+    }
+    fs::write(path, package)?;
+    Ok(())
+}
+
+fn remove_fetched(dir: &Path) -> io::Result<()> {
+    let path = dir.join("package.toml");
+    for file in [signature::signature_path(&path), path] {
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Download a file, None if the server doesn't have it. `file://` urls are read from disk.
+async fn get(client: &Client, url: &str) -> Result<Option<Vec<u8>>, failure::Error> {
+    let parsed = Url::parse(url)?;
+    if parsed.scheme() == "file" {
+        let path: PathBuf = parsed
+            .to_file_path()
+            .map_err(|_| failure::err_msg(format!("'{}' is not a valid file url", url)))?;
+        return match fs::read(path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        };
+    }
+
+    let response = client.get(url).send().await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.bytes().await?.to_vec()))
+}
+
+/// Run a future to completion from synchronous code, whether or not a runtime is running.
+fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to start runtime")
+            .block_on(future)
+    })
+    .join()
+    .expect("Fetching package failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        packages::{LazyPackages, Packages, RemotePackages},
+        testing::{package, write_package},
+    };
+
+    fn publish(repo: &Path, name: &str, version: &str) {
+        write_package(&repo.join(name).join(version), &package(name, version));
+    }
+
+    async fn write_index(repo: &Path) {
+        let index = Index::scan(repo).await.unwrap();
+        fs::write(repo.join(INDEX_FILE), toml::to_string(&index).unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn packages_are_fetched_on_demand() {
+        let server = tempfile::tempdir().unwrap();
+        publish(server.path(), "tool", "1.0.0");
+        publish(server.path(), "tool", "1.1.0");
+        publish(server.path(), "other", "0.1.0");
+        write_index(server.path()).await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let url = Url::from_directory_path(server.path()).unwrap();
+        sync(url.as_str(), cache.path()).await.unwrap();
+        assert!(!cache.path().join("tool/1.1.0/package.toml").exists());
+
+        let packages = LazyPackages {
+            dir: cache.path().to_path_buf(),
+        };
+        let mut get_package = packages.get_package("tool").unwrap();
+        assert_eq!(get_package.versions(), vec!["1.0.0", "1.1.0"]);
+        let package = get_package.latest().unwrap().package().unwrap();
+        assert_eq!(package.version, "1.1.0");
+        assert!(cache.path().join("tool/1.1.0/package.toml").exists());
+        assert!(!cache.path().join("tool/1.0.0/package.toml").exists());
+
+        // Removed packages disappear on the next sync
+        fs::remove_dir_all(server.path().join("other")).unwrap();
+        write_index(server.path()).await;
+        sync(url.as_str(), cache.path()).await.unwrap();
+        assert!(packages.get_package("other").is_none());
+    }
+
+    #[tokio::test]
+    async fn republished_versions_are_fetched_again() {
+        let server = tempfile::tempdir().unwrap();
+        publish(server.path(), "tool", "1.0.0");
+        publish(server.path(), "other", "0.1.0");
+        write_index(server.path()).await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let url = Url::from_directory_path(server.path()).unwrap();
+        sync(url.as_str(), cache.path()).await.unwrap();
+        let packages = RemotePackages {
+            remotes: vec![("main".to_string(), cache.path().to_path_buf())],
+        };
+        // Every latest version is downloaded up front
+        assert_eq!(packages.load_latest().await.unwrap().len(), 2);
+        assert!(cache.path().join("other/0.1.0/package.toml").exists());
+
+        let published = server.path().join("tool/1.0.0/package.toml");
+        let mut contents = fs::read_to_string(&published).unwrap();
+        contents.push_str("# republished\n");
+        fs::write(&published, &contents).unwrap();
+        let cached = cache.path().join("tool/1.0.0");
+
+        // The old copy is rejected until the index says it changed
+        fs::remove_file(cached.join("package.toml")).unwrap();
+        assert!(fetch_missing(&cached).is_err());
+
+        write_index(server.path()).await;
+        sync(url.as_str(), cache.path()).await.unwrap();
+        fetch_missing(&cached).unwrap();
+        assert_eq!(
+            fs::read_to_string(cached.join("package.toml")).unwrap(),
+            contents
+        );
+        assert!(cache.path().join("other/0.1.0/package.toml").exists());
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use crate::{
+    package::Package,
+    packages::{self, Packages},
+    Config,
+};
+
+/// Everything known about one package.
+#[derive(Debug)]
+pub struct PackageInfo {
+    pub name: String,
+    /// Every version folder of the package in the repository.
+    pub versions: Vec<String>,
+    /// The version that would be installed by default.
+    pub latest: Option<Package>,
+    /// Remote the latest version comes from.
+    pub remote: Option<String>,
+    pub installed: Option<InstalledInfo>,
+}
+
+/// Install state of a package.
+#[derive(Debug)]
+pub struct InstalledInfo {
+    pub package: Package,
+    pub location: PathBuf,
+    /// Remote it was installed from, None if it isn't known.
+    pub remote: Option<String>,
+    /// Links created in the bin folder.
+    pub links: Vec<PathBuf>,
+}
+
+/// Collect information about a package from the remotes and local install.
+/// name can be given as `remote/name` to look at a specific remote.
+pub async fn info(config: &Config, name: &str) -> Result<PackageInfo, failure::Error> {
+    let available = packages::remote_packages(config).get_package(name);
+    let name = packages::split_name(name).1;
+    let installed = packages::installed_packages()
+        .lazy()
+        .await?
+        .get_package(name);
+    if available.is_none() && installed.is_none() {
+        return Err(failure::err_msg(format!("'{}' does not exist", name)));
+    }
+
+    let (versions, latest, remote) = match available {
+        Some(mut get_package) => (
+            get_package.versions(),
+            get_package.latest().and_then(|x| x.package().ok()),
+            get_package.remote,
+        ),
+        None => (vec![], None, None),
+    };
+
+    let installed = installed.and_then(|get_package| {
+        let package = get_package.package().ok()?;
+        let manifest = get_package.files().unwrap_or_default();
+        Some(InstalledInfo {
+            package,
+            location: get_package.dir,
+            remote: manifest.remote,
+            links: manifest.links,
+        })
+    });
+
+    Ok(PackageInfo {
+        name: name.to_string(),
+        versions,
+        latest,
+        remote,
+        installed,
+    })
+}
@@ -0,0 +1,274 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use reqwest::Client;
+
+use crate::{
+    download_mirrors,
+    jellyfish_install::{self, BasicJellyFishInstaller},
+    manifest::{sha256_file, Manifest, ManifestEntry},
+    package::{Package, PackageType},
+    packages::GetPackage,
+    proj_dirs,
+    transaction::Transaction,
+    Config, Downloaded,
+};
+
+/// Directory a package is installed into.
+pub fn package_dir(name: &str) -> PathBuf {
+    proj_dirs().data_dir().join("packages").join(name)
+}
+
+/// Directory the binaries of installed packages are linked into.
+pub fn bin_dir() -> PathBuf {
+    proj_dirs().data_dir().join("bin")
+}
+
+/// Download the artifact of a package into the cache directory, verifying its checksum if one is declared.
+/// Mirrors are tried in turn until one succeeds.
+pub async fn download(config: &Config, package: &Package) -> Result<Downloaded, failure::Error> {
+    let cache_dir = proj_dirs().cache_dir().to_path_buf();
+    if !cache_dir.exists() {
+        fs::create_dir_all(&cache_dir)?;
+    }
+    download_mirrors(
+        &Client::new(),
+        &config.mirror_urls(&package.install),
+        cache_dir.join(format!("{}-{}.jellyfish", package.name, package.version)),
+        package.install.sha256.as_deref(),
+    )
+    .await
+}
+
+/// Download and install a package from remote, replacing any installed version of it.
+/// Returns the links created in the bin folder.
+pub async fn install(
+    config: &Config,
+    package: &Package,
+    remote: Option<&str>,
+) -> Result<Vec<PathBuf>, failure::Error> {
+    let downloaded = download(config, package).await?;
+    install_file(config, package, remote, downloaded.path)
+}
+
+/// Install an already downloaded package artifact, replacing any installed version of it.
+/// The remote it came from is recorded in its manifest.
+/// Returns the links created in the bin folder.
+pub fn install_file(
+    config: &Config,
+    package: &Package,
+    remote: Option<&str>,
+    file: PathBuf,
+) -> Result<Vec<PathBuf>, failure::Error> {
+    package.check_voran_version()?;
+    match package.install.type_ {
+        PackageType::Executable => {
+            process::Command::new(file).spawn()?;
+            Ok(vec![])
+        }
+        PackageType::JellyFish | PackageType::Wharf => {
+            let installer = BasicJellyFishInstaller::new(file)
+                .link_mode(config.link_mode)
+                .format(package.install.format)
+                .limits(config.extract_limits);
+            let mut transaction = Transaction::begin(package_dir(&package.name), bin_dir())?;
+            match install_staged(&installer, package, remote, &mut transaction) {
+                Ok(links) => {
+                    transaction.commit()?;
+                    Ok(links)
+                }
+                Err(err) => {
+                    if let Err(rollback_err) = transaction.rollback() {
+                        return Err(failure::err_msg(format!(
+                            "{} (rolling back also failed: {})",
+                            err, rollback_err
+                        )));
+                    }
+                    Err(err)
+                }
+            }
+        }
+    }
+}
+
+/// Extract a package into the transaction's staging folder, then swap it in and link it.
+fn install_staged(
+    installer: &BasicJellyFishInstaller,
+    package: &Package,
+    remote: Option<&str>,
+    transaction: &mut Transaction,
+) -> Result<Vec<PathBuf>, failure::Error> {
+    let staging = transaction.staging_dir().to_path_buf();
+    jellyfish_install::install_to(installer, staging.clone(), bin_dir(), false)?;
+
+    let mut manifest = Manifest::load(&staging)?;
+    manifest.remote = remote.map(str::to_string);
+    manifest.save(&staging)?;
+
+    // Store package information with package for later use.
+    fs::write(staging.join("package.toml"), toml::to_string(package)?)?;
+
+    transaction.activate()?;
+    let links = transaction.link(installer)?;
+
+    if let PackageType::Wharf = package.install.type_ {
+        wharf::run(package_dir(&package.name).join("build.rope"));
+    }
+
+    Ok(links)
+}
+
+/// Uninstall a package: reverse its Wharf script, remove its links and then its files.
+/// Returns the package that was removed.
+pub fn uninstall(name: &str) -> Result<Package, failure::Error> {
+    let dir = package_dir(name);
+    if !dir.exists() {
+        return Err(failure::err_msg(format!("'{}' is not installed", name)));
+    }
+    let package = GetPackage {
+        dir: dir.clone(),
+        remote: None,
+    }
+    .package()?;
+    if let PackageType::Executable = package.install.type_ {
+        return Err(failure::err_msg("This package cannot be uninstalled"));
+    }
+
+    remove_installed(&dir)?;
+    Ok(package)
+}
+
+/// Undo the Wharf script of an installed package (if any), remove its links and then its files.
+fn remove_installed(dir: &Path) -> Result<(), failure::Error> {
+    let old = GetPackage {
+        dir: dir.to_path_buf(),
+        remote: None,
+    };
+    if let Ok(package) = old.package() {
+        if let PackageType::Wharf = package.install.type_ {
+            wharf::reverse(dir.join("build.rope"));
+        }
+    }
+
+    for link in package_links(dir)? {
+        jellyfish_install::remove_link(&link)?;
+    }
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+/// Links in the bin folder that belong to an installed package.
+///
+/// Links recorded in the manifest are skipped if another package has since replaced them.
+/// Packages installed before manifests existed fall back to symlinks pointing into the package.
+fn package_links(dir: &Path) -> Result<Vec<PathBuf>, failure::Error> {
+    let manifest = Manifest::load(dir).ok();
+    let candidates = match &manifest {
+        Some(manifest) => manifest.links.clone(),
+        None if bin_dir().is_dir() => bin_dir()
+            .read_dir()?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?,
+        None => vec![],
+    };
+    Ok(candidates
+        .into_iter()
+        .filter(|link| match link.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => fs::read_link(link)
+                .map(|target| target.starts_with(dir))
+                .unwrap_or(false),
+            // Copies and hardlinks can only be recognised through the manifest
+            Ok(_) => manifest
+                .as_ref()
+                .map(|manifest| is_own_copy(dir, manifest, link))
+                .unwrap_or(false),
+            Err(_) => false,
+        })
+        .collect())
+}
+
+/// Whether a copied or hardlinked bin entry is still the one the package created,
+/// rather than one another package has since put in its place.
+fn is_own_copy(dir: &Path, manifest: &Manifest, link: &Path) -> bool {
+    let relative = match link.file_name() {
+        Some(name) => Path::new("bin").join(name),
+        None => return false,
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let (Ok(a), Ok(b)) = (fs::metadata(link), fs::metadata(dir.join(&relative))) {
+            if a.is_file() && a.dev() == b.dev() && a.ino() == b.ino() {
+                return true;
+            }
+        }
+    }
+
+    // Compare against the checksums recorded when the package was installed
+    let recorded: Vec<_> = manifest
+        .files
+        .iter()
+        .filter(|x| x.sha256.is_some() && x.path.starts_with(&relative))
+        .map(|x| {
+            (
+                x.path.strip_prefix(&relative).unwrap().to_path_buf(),
+                &x.sha256,
+            )
+        })
+        .collect();
+    let actual = if link.is_dir() {
+        match Manifest::scan(link) {
+            Ok(scanned) => scanned.files,
+            Err(_) => return false,
+        }
+    } else {
+        match sha256_file(link) {
+            Ok(sha256) => vec![ManifestEntry {
+                path: PathBuf::new(),
+                sha256: Some(sha256),
+            }],
+            Err(_) => return false,
+        }
+    };
+    let actual: Vec<_> = actual
+        .iter()
+        .filter(|x| x.sha256.is_some())
+        .map(|x| (x.path.clone(), &x.sha256))
+        .collect();
+    !recorded.is_empty() && recorded == actual
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaced_copies_are_not_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("packages/tool");
+        let bin = dir.path().join("bin");
+        fs::create_dir_all(package.join("bin")).unwrap();
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(package.join("bin/tool"), "tool").unwrap();
+        let manifest = Manifest::scan(&package).unwrap();
+
+        fs::copy(package.join("bin/tool"), bin.join("tool")).unwrap();
+        assert!(is_own_copy(&package, &manifest, &bin.join("tool")));
+
+        // Another package installed its own tool over it
+        fs::write(bin.join("tool"), "other").unwrap();
+        assert!(!is_own_copy(&package, &manifest, &bin.join("tool")));
+
+        #[cfg(unix)]
+        {
+            fs::remove_file(bin.join("tool")).unwrap();
+            fs::hard_link(package.join("bin/tool"), bin.join("tool")).unwrap();
+            assert!(is_own_copy(&package, &manifest, &bin.join("tool")));
+        }
+    }
+}
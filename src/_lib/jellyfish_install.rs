@@ -1,11 +1,18 @@
 use std::{
     fs::{self, File},
-    os::windows,
+    io::{self, Read},
     path::{Path, PathBuf},
 };
 
 use flate2::read::GzDecoder;
-use tar::Archive;
+use serde::{Deserialize, Serialize};
+use xz2::read::XzDecoder;
+
+use crate::{
+    extract::{self, ExtractLimits},
+    manifest::Manifest,
+    package::ArchiveFormat,
+};
 
 #[cfg(test)]
 use mockall::automock;
@@ -13,12 +20,25 @@ use mockall::automock;
 #[cfg_attr(test, automock)]
 pub trait JellyFishInstaller {
     fn extract(&self, out: PathBuf) -> Result<(), failure::Error>;
-    fn link_to(&self, out: PathBuf, bin_path: PathBuf) -> Result<(), failure::Error>;
+    /// Link the files in out/bin into bin_path, returning the links that were created.
+    fn link_to(&self, out: PathBuf, bin_path: PathBuf) -> Result<Vec<PathBuf>, failure::Error>;
+}
+
+/// How package binaries are placed in the bin folder.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum LinkMode {
+    #[default]
+    Symlink,
+    Hardlink,
+    Copy,
 }
 
 /// Used to install JellyFish packages.
 pub struct BasicJellyFishInstaller {
     input_file: PathBuf,
+    link_mode: LinkMode,
+    format: Option<ArchiveFormat>,
+    limits: ExtractLimits,
 }
 
 impl BasicJellyFishInstaller {
@@ -26,24 +46,67 @@ impl BasicJellyFishInstaller {
     pub fn new<P: AsRef<Path> + 'static>(input: P) -> Self {
         Self {
             input_file: input.as_ref().to_path_buf(),
+            link_mode: LinkMode::default(),
+            format: None,
+            limits: ExtractLimits::default(),
+        }
+    }
+
+    /// Set the limits on how much a package may extract.
+    pub fn limits(mut self, limits: ExtractLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the archive format, if None it is detected from the file contents.
+    pub fn format(mut self, format: Option<ArchiveFormat>) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set how binaries are placed in the bin folder (symlinks by default).
+    pub fn link_mode(mut self, link_mode: LinkMode) -> Self {
+        self.link_mode = link_mode;
+        self
+    }
+
+    fn link(&self, target: &Path, link: &Path) -> io::Result<()> {
+        match self.link_mode {
+            LinkMode::Symlink => symlink(target, link),
+            LinkMode::Hardlink if target.is_dir() => copy_dir(target, link, true),
+            LinkMode::Hardlink => fs::hard_link(target, link),
+            LinkMode::Copy if target.is_dir() => copy_dir(target, link, false),
+            LinkMode::Copy => fs::copy(target, link).map(|_| ()),
         }
     }
 }
 
 impl JellyFishInstaller for BasicJellyFishInstaller {
     fn extract(&self, out: PathBuf) -> Result<(), failure::Error> {
+        let format = match self.format {
+            Some(format) => format,
+            None => detect_format(&self.input_file)?,
+        };
         let file = File::open(&self.input_file)?;
-        let tar = GzDecoder::new(file);
-        let mut archive = Archive::new(tar);
-        archive.unpack(&out)?;
-        Ok(())
+        let tar: Box<dyn Read> = match format {
+            ArchiveFormat::TarGz => Box::new(GzDecoder::new(file)),
+            ArchiveFormat::TarXz => Box::new(XzDecoder::new(file)),
+            ArchiveFormat::TarZst => Box::new(zstd::Decoder::new(file)?),
+            ArchiveFormat::Tar => Box::new(file),
+            ArchiveFormat::Zip => {
+                extract::unpack_zip(file, &out, self.limits)?;
+                return finish_extract(&out);
+            }
+        };
+        extract::unpack_tar(tar, &out, self.limits)?;
+        finish_extract(&out)
     }
 
-    fn link_to(&self, out: PathBuf, bin_path: PathBuf) -> Result<(), failure::Error> {
+    fn link_to(&self, out: PathBuf, bin_path: PathBuf) -> Result<Vec<PathBuf>, failure::Error> {
         let bin = out.join("bin");
         if !bin.exists() {
             // Package doesn't have any binaries
-            return Ok(());
+            return Ok(vec![]);
         }
 
         if !bin_path.exists() {
@@ -54,6 +117,7 @@ impl JellyFishInstaller for BasicJellyFishInstaller {
         }
 
         // Iterate over bin files
+        let mut links = vec![];
         for bin_file in bin
             .read_dir()
             .expect("Failed to read package bin directory")
@@ -61,29 +125,128 @@ impl JellyFishInstaller for BasicJellyFishInstaller {
             let file = bin_file?;
 
             let link = bin_path.join(file.file_name());
-            if link.exists() {
-                fs::remove_file(link).unwrap();
-            }
-            #[cfg(windows)]
-            {
-                if file.path().is_dir() {
-                    windows::fs::symlink_dir(file.path(), bin_path.join(file.file_name()))?;
-                } else if file.path().is_file() {
-                    windows::fs::symlink_file(file.path(), bin_path.join(file.file_name()))?;
-                }
+            // symlink_metadata also finds dangling links
+            if link.symlink_metadata().is_ok() {
+                remove_link(&link)?;
             }
+            self.link(&file.path(), &link)?;
+            links.push(link);
         }
-        Ok(())
+        Ok(links)
+    }
+}
+
+/// Detect the archive format of a file from its magic bytes.
+pub fn detect_format(path: &Path) -> Result<ArchiveFormat, failure::Error> {
+    let mut magic = vec![];
+    File::open(path)?.take(262).read_to_end(&mut magic)?;
+
+    if magic.starts_with(&[0x1f, 0x8b]) {
+        Ok(ArchiveFormat::TarGz)
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Ok(ArchiveFormat::TarXz)
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Ok(ArchiveFormat::TarZst)
+    } else if magic.starts_with(b"PK\x03\x04") || magic.starts_with(b"PK\x05\x06") {
+        Ok(ArchiveFormat::Zip)
+    } else if magic.len() == 262 && &magic[257..262] == b"ustar" {
+        Ok(ArchiveFormat::Tar)
+    } else {
+        Err(failure::err_msg(format!(
+            "'{}' is not a supported archive",
+            path.display()
+        )))
+    }
+}
+
+/// Fix up an extracted package.
+fn finish_extract(out: &Path) -> Result<(), failure::Error> {
+    // Archives built on Windows don't carry executable bits.
+    #[cfg(unix)]
+    {
+        let bin = out.join("bin");
+        if bin.is_dir() {
+            make_executable(&bin)?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = out;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    if target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
     }
 }
 
-/// Extract files from package then link files in /bin to specified bin_path, if link is set to true
+/// Remove a link (or copy) created by link_to.
+pub(crate) fn remove_link(link: &Path) -> io::Result<()> {
+    let metadata = link.symlink_metadata()?;
+    if metadata.is_dir() {
+        fs::remove_dir_all(link)
+    } else if cfg!(windows) && metadata.file_type().is_symlink() && link.is_dir() {
+        // Directory symlinks are directories on Windows
+        fs::remove_dir(link)
+    } else {
+        fs::remove_file(link)
+    }
+}
+
+/// Recursively copy (or hardlink) a directory.
+fn copy_dir(from: &Path, to: &Path, hardlink: bool) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in from.read_dir()? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target, hardlink)?;
+        } else if hardlink {
+            fs::hard_link(entry.path(), target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Give every file in a directory the executable bit wherever it is readable.
+#[cfg(unix)]
+fn make_executable(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            make_executable(&entry.path())?;
+        } else if file_type.is_file() {
+            let mut permissions = entry.metadata()?.permissions();
+            let mode = permissions.mode();
+            permissions.set_mode(mode | ((mode & 0o444) >> 2));
+            fs::set_permissions(entry.path(), permissions)?;
+        }
+    }
+    Ok(())
+}
+
+/// Extract files from package then link files in /bin to specified bin_path, if link is set to true.
+/// A manifest of the extracted files and created links is written to out.
+/// Returns the links that were created.
 pub fn install_to<P: AsRef<Path> + 'static>(
     installer: &impl JellyFishInstaller,
     out: P,
     bin_path: P,
     link: bool,
-) -> Result<(), failure::Error> {
+) -> Result<Vec<PathBuf>, failure::Error> {
     if !out.as_ref().exists() {
         fs::create_dir_all(&out)?;
     }
@@ -93,13 +256,16 @@ pub fn install_to<P: AsRef<Path> + 'static>(
 
     // Extract the compressed file.
     installer.extract(out.as_ref().to_path_buf())?;
+    let mut manifest = Manifest::scan(out.as_ref())?;
 
     // Make a symbolic link with the bin files inside bin_path
     if link {
-        installer.link_to(out.as_ref().to_path_buf(), bin_path.as_ref().to_path_buf())?;
+        manifest.links =
+            installer.link_to(out.as_ref().to_path_buf(), bin_path.as_ref().to_path_buf())?;
     }
 
-    Ok(())
+    manifest.save(out.as_ref())?;
+    Ok(manifest.links)
 }
 
 #[cfg(test)]
@@ -111,11 +277,98 @@ mod tests {
     async fn install_to_calls_right_functions() {
         let mut mock = MockJellyFishInstaller::default();
         mock.expect_extract().times(1).returning(|_| Ok(()));
-        mock.expect_link_to().times(1).returning(|_, _| Ok(()));
+        mock.expect_link_to().times(1).returning(|_, _| Ok(vec![]));
         install_to(&mock, "out/", "bin/", true).unwrap();
         mock.checkpoint();
 
         // Cleanup
         fs::remove_dir_all("out/").unwrap();
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn install_to_links_executable_binaries() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("package.jellyfish");
+
+        // Build a package with one non-executable binary.
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bin/tool", &b"hi"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let out = dir.path().join("out");
+        let bin = dir.path().join("bin");
+        let installer = BasicJellyFishInstaller::new(archive);
+        let links = install_to(&installer, out.clone(), bin.clone(), true).unwrap();
+
+        assert_eq!(links, vec![bin.join("tool")]);
+        assert!(bin.join("tool").symlink_metadata().unwrap().is_symlink());
+        let mode = fs::metadata(out.join("bin/tool"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o111, 0o111);
+
+        let manifest = Manifest::load(&out).unwrap();
+        assert_eq!(manifest.links, links);
+        let paths: Vec<_> = manifest.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("bin"), PathBuf::from("bin/tool")]);
+        assert!(manifest.modified(&out).is_empty());
+    }
+
+    #[test]
+    fn extracts_every_archive_format() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut tar = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        header.set_mode(0o755);
+        header.set_cksum();
+        tar.append_data(&mut header, "bin/tool", &b"hi"[..])
+            .unwrap();
+        let tar = tar.into_inner().unwrap();
+
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gz.write_all(&tar).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(vec![], 6);
+        xz.write_all(&tar).unwrap();
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(vec![]));
+        zip.start_file("bin/tool", Default::default()).unwrap();
+        zip.write_all(b"hi").unwrap();
+
+        let archives = vec![
+            (ArchiveFormat::TarGz, gz.finish().unwrap()),
+            (ArchiveFormat::TarXz, xz.finish().unwrap()),
+            (
+                ArchiveFormat::TarZst,
+                zstd::encode_all(&tar[..], 0).unwrap(),
+            ),
+            (ArchiveFormat::Tar, tar.clone()),
+            (ArchiveFormat::Zip, zip.finish().unwrap().into_inner()),
+        ];
+        for (format, contents) in archives {
+            let archive = dir.path().join(format!("{:?}.jellyfish", format));
+            fs::write(&archive, contents).unwrap();
+            assert_eq!(detect_format(&archive).unwrap(), format);
+
+            let out = dir.path().join(format!("{:?}", format));
+            BasicJellyFishInstaller::new(archive)
+                .extract(out.clone())
+                .unwrap();
+            assert_eq!(fs::read_to_string(out.join("bin/tool")).unwrap(), "hi");
+        }
+    }
 }
@@ -0,0 +1,188 @@
+use std::{collections::BTreeMap, path::Path};
+
+use reqwest::Client;
+use semver::{Version, VersionReq};
+
+use crate::{
+    download_file,
+    package::Package,
+    packages::{GetPackage, GetPackages},
+};
+
+/// Problems found in one package of a repository.
+#[derive(Debug)]
+pub struct LintReport {
+    /// Name of the package folder.
+    pub name: String,
+    pub problems: Vec<String>,
+}
+
+/// Check every package of a repository for mistakes that would only show up on install.
+///
+/// If check_hashes is set every artifact url (mirrors included) is downloaded and compared
+/// against the declared sha256.
+pub async fn lint(dir: &Path, check_hashes: bool) -> Result<Vec<LintReport>, failure::Error> {
+    let mut reports = vec![];
+    // Package folders differing only in case clash on case-insensitive file systems.
+    let mut names: BTreeMap<String, String> = BTreeMap::new();
+
+    for get_package in GetPackages::new(dir).load().await? {
+        let name = get_package
+            .dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let mut problems = vec![];
+        if let Some(other) = names.insert(name.to_lowercase(), name.clone()) {
+            problems.push(format!("Has the same name as '{}' apart from case", other));
+        }
+
+        let packages = lint_versions(&get_package, &name, &mut problems);
+        if check_hashes {
+            let client = Client::new();
+            for package in &packages {
+                check_hash(&client, package, &mut problems).await;
+            }
+        }
+
+        reports.push(LintReport { name, problems });
+    }
+    reports.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(reports)
+}
+
+/// Check the version folders of a package, returning the packages that loaded.
+fn lint_versions(get_package: &GetPackage, name: &str, problems: &mut Vec<String>) -> Vec<Package> {
+    let versions = get_package.versions();
+    let has_semver = versions.iter().any(|x| Version::parse(x).is_ok());
+    if versions.is_empty() {
+        problems.push("Has no version folders".to_string());
+    } else if !has_semver && !versions.iter().any(|x| x == "LATEST") {
+        problems.push("Has no semver version folders and no LATEST folder".to_string());
+    }
+
+    let mut packages = vec![];
+    // Version -> folder declaring it
+    let mut declared: BTreeMap<String, String> = BTreeMap::new();
+    for folder in &versions {
+        let version = GetPackage {
+            dir: get_package.dir.join(folder),
+            remote: None,
+        };
+        let package = match version.package() {
+            Ok(package) => package,
+            Err(err) => {
+                problems.push(err.to_string());
+                continue;
+            }
+        };
+
+        if package.name != name {
+            problems.push(format!(
+                "{}/package.toml has name '{}', expected '{}'",
+                folder, package.name, name
+            ));
+        }
+        if folder == "LATEST" {
+            if let Some(highest) = get_package.highest_matching(&VersionReq::STAR) {
+                if highest != package.version {
+                    problems.push(format!(
+                        "LATEST is v{}, but the highest version is v{}",
+                        package.version, highest
+                    ));
+                }
+            }
+        } else {
+            if &package.version != folder {
+                problems.push(format!(
+                    "{}/package.toml has version '{}', expected '{}'",
+                    folder, package.version, folder
+                ));
+            }
+            if Version::parse(folder).is_err() {
+                problems.push(format!(
+                    "Version folder '{}' is not a semver version",
+                    folder
+                ));
+            }
+            if let Some(other) = declared.insert(package.version.clone(), folder.clone()) {
+                problems.push(format!(
+                    "v{} is declared by both '{}' and '{}'",
+                    package.version, other, folder
+                ));
+            }
+        }
+        packages.push(package);
+    }
+    packages
+}
+
+/// Download every url of a package and compare it against the declared checksum.
+async fn check_hash(client: &Client, package: &Package, problems: &mut Vec<String>) {
+    let expected = match &package.install.sha256 {
+        Some(sha256) => sha256,
+        None => {
+            problems.push(format!("v{} has no sha256", package.version));
+            return;
+        }
+    };
+    let dir = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(err) => {
+            problems.push(err.to_string());
+            return;
+        }
+    };
+    for url in package.install.urls() {
+        if let Err(err) =
+            download_file(client, &url, dir.path().join("artifact"), Some(expected)).await
+        {
+            problems.push(format!("v{}: {}", package.version, err));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testing::{package, write_package};
+
+    fn write(repo: &Path, folder: &str, name: &str, version: &str) {
+        write_package(&repo.join(folder), &package(name, version));
+    }
+
+    #[tokio::test]
+    async fn reports_problems_per_package() {
+        let repo = tempfile::tempdir().unwrap();
+        write(repo.path(), "good/1.0.0", "good", "1.0.0");
+        write(repo.path(), "good/1.1.0", "good", "1.1.0");
+        write(repo.path(), "bad/1.0.0", "bad", "1.0.1");
+        write(repo.path(), "bad/2.0.0", "other", "2.0.0");
+        write(repo.path(), "bad/LATEST", "bad", "1.0.0");
+        fs::create_dir_all(repo.path().join("empty")).unwrap();
+
+        let reports = lint(repo.path(), false).await.unwrap();
+        let reports: Vec<_> = reports
+            .iter()
+            .map(|x| (x.name.as_str(), x.problems.clone()))
+            .collect();
+        assert_eq!(
+            reports,
+            vec![
+                (
+                    "bad",
+                    vec![
+                        "1.0.0/package.toml has version '1.0.1', expected '1.0.0'".to_string(),
+                        "2.0.0/package.toml has name 'other', expected 'bad'".to_string(),
+                        "LATEST is v1.0.0, but the highest version is v2.0.0".to_string(),
+                    ]
+                ),
+                ("empty", vec!["Has no version folders".to_string()]),
+                ("good", vec![]),
+            ]
+        );
+    }
+}
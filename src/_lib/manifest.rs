@@ -0,0 +1,104 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Name of the manifest file stored in an installed package's folder.
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+/// Record of every file and link an installed package owns.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct Manifest {
+    /// Remote the package was installed from, None if it isn't known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    /// Links created in the bin folder.
+    #[serde(default)]
+    pub links: Vec<PathBuf>,
+    /// Extracted files, relative to the package folder.
+    pub files: Vec<ManifestEntry>,
+}
+
+/// A single extracted path.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    /// Hex encoded sha256 checksum, None for directories and symlinks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl Manifest {
+    /// Record every path below dir.
+    pub fn scan(dir: &Path) -> io::Result<Self> {
+        let mut files = vec![];
+        scan_dir(dir, dir, &mut files)?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self {
+            remote: None,
+            links: vec![],
+            files,
+        })
+    }
+
+    /// Load the manifest of an installed package.
+    pub fn load(dir: &Path) -> Result<Self, failure::Error> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Err(failure::err_msg(format!("'{}' not found", path.display())));
+        }
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Store the manifest in an installed package's folder.
+    pub fn save(&self, dir: &Path) -> Result<(), failure::Error> {
+        fs::write(dir.join(MANIFEST_FILE), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Files below dir that are missing or no longer match their checksum.
+    pub fn modified(&self, dir: &Path) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|entry| match &entry.sha256 {
+                Some(expected) => sha256_file(&dir.join(&entry.path))
+                    .map(|actual| &actual != expected)
+                    .unwrap_or(true),
+                None => dir.join(&entry.path).symlink_metadata().is_err(),
+            })
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+}
+
+/// Hex encoded sha256 checksum of a file.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn scan_dir(root: &Path, dir: &Path, files: &mut Vec<ManifestEntry>) -> io::Result<()> {
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        let sha256 = if file_type.is_file() {
+            Some(sha256_file(&path)?)
+        } else {
+            None
+        };
+        files.push(ManifestEntry {
+            path: path.strip_prefix(root).unwrap().to_path_buf(),
+            sha256,
+        });
+        if file_type.is_dir() {
+            scan_dir(root, &path, files)?;
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,22 @@
 mod download;
+pub mod extract;
+pub mod git;
+pub mod index;
+pub mod info;
+pub mod install;
 pub mod jellyfish_install;
+pub mod lint;
+pub mod manifest;
+pub mod pack;
 pub mod package;
 pub mod packages;
+pub mod publish;
+pub mod remotes;
+pub mod resolve;
+pub mod search;
+pub mod signature;
+#[cfg(test)]
+pub mod testing;
+pub mod transaction;
 pub mod update;
 pub use download::*;
@@ -0,0 +1,272 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use tar::{Builder, EntryType, Header};
+
+use crate::{
+    manifest::sha256_file,
+    package::{ArchiveFormat, InstallInfo, Package, PackageType, SCHEMA_VERSION},
+};
+
+/// Modification time given to every entry, so archives only depend on file contents.
+const PACK_MTIME: u64 = 0;
+
+/// What to put in the package.toml of a packed package.
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    pub name: String,
+    pub friendly_name: String,
+    pub version: String,
+    /// Url the archive will be published at, `{name}` and `{version}` are replaced.
+    pub url: String,
+}
+
+/// A package built by pack.
+#[derive(Debug)]
+pub struct Packed {
+    pub archive: PathBuf,
+    pub package_toml: PathBuf,
+    pub package: Package,
+}
+
+/// Build `{name}.jellyfish` and a matching package.toml in out from a folder with a bin folder.
+///
+/// The archive is reproducible: entries are sorted and mtimes, owners and permissions are
+/// normalised, so packing the same files twice gives the same checksum.
+pub fn pack(dir: &Path, out: &Path, options: &PackOptions) -> Result<Packed, failure::Error> {
+    if !dir.join("bin").is_dir() {
+        return Err(failure::err_msg(format!(
+            "'{}' has no bin folder",
+            dir.display()
+        )));
+    }
+
+    fs::create_dir_all(out)?;
+    let archive_name = format!("{}.jellyfish", options.name);
+    let archive = out.join(&archive_name);
+
+    let mut entries = vec![];
+    collect_entries(dir, Path::new(""), &mut entries)?;
+    // Packing into the folder being packed must not pick up the previous output
+    if let Ok(relative) = out.canonicalize()?.strip_prefix(dir.canonicalize()?) {
+        let outputs = [relative.join(&archive_name), relative.join("package.toml")];
+        entries.retain(|x| !outputs.contains(x));
+    }
+    entries.sort();
+
+    write_archive(dir, &entries, &archive)?;
+
+    let url = options
+        .url
+        .replace("{name}", &options.name)
+        .replace("{version}", &options.version);
+    let package = Package {
+        schema: SCHEMA_VERSION,
+        name: options.name.clone(),
+        friendly_name: options.friendly_name.clone(),
+        version: options.version.clone(),
+        description: None,
+        tags: vec![],
+        homepage: None,
+        repository: None,
+        license: None,
+        authors: vec![],
+        min_voran_version: None,
+        install: InstallInfo {
+            url,
+            mirrors: vec![],
+            type_: PackageType::JellyFish,
+            format: Some(ArchiveFormat::TarGz),
+            sha256: Some(sha256_file(&archive)?),
+        },
+        dependencies: Default::default(),
+    };
+    let package_toml = out.join("package.toml");
+    package.validate(package_toml.clone())?;
+    fs::write(&package_toml, toml::to_string(&package)?)?;
+
+    Ok(Packed {
+        archive,
+        package_toml,
+        package,
+    })
+}
+
+/// Paths of every file, folder and symlink under dir, relative to root.
+fn collect_entries(root: &Path, dir: &Path, entries: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in root.join(dir).read_dir()? {
+        let entry = entry?;
+        let relative = dir.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_entries(root, &relative, entries)?;
+        }
+        entries.push(relative);
+    }
+    Ok(())
+}
+
+fn write_archive(root: &Path, entries: &[PathBuf], archive: &Path) -> Result<(), failure::Error> {
+    let encoder = GzEncoder::new(File::create(archive)?, Compression::best());
+    let mut builder = Builder::new(encoder);
+    for relative in entries {
+        let path = root.join(relative);
+        let metadata = path.symlink_metadata()?;
+        let mut header = Header::new_gnu();
+        header.set_mtime(PACK_MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_username("")?;
+        header.set_groupname("")?;
+
+        if metadata.file_type().is_symlink() {
+            header.set_entry_type(EntryType::Symlink);
+            header.set_mode(0o777);
+            header.set_size(0);
+            builder.append_link(&mut header, relative, fs::read_link(&path)?)?;
+        } else if metadata.is_dir() {
+            header.set_entry_type(EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            builder.append_data(&mut header, relative, io::empty())?;
+        } else {
+            header.set_entry_type(EntryType::Regular);
+            header.set_mode(if is_executable(relative, &metadata) {
+                0o755
+            } else {
+                0o644
+            });
+            header.set_size(metadata.len());
+            builder.append_data(&mut header, relative, File::open(&path)?)?;
+        }
+    }
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(())
+}
+
+/// Everything in bin is executable, elsewhere the executable bit is kept where there is one.
+fn is_executable(relative: &Path, metadata: &fs::Metadata) -> bool {
+    if relative.starts_with("bin") {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::extract::{unpack_tar, ExtractLimits};
+
+    fn options() -> PackOptions {
+        PackOptions {
+            name: "tool".to_string(),
+            friendly_name: "Tool".to_string(),
+            version: "1.2.0".to_string(),
+            url: "https://example.com/releases/{version}/{name}.jellyfish".to_string(),
+        }
+    }
+
+    fn source() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("bin")).unwrap();
+        fs::create_dir_all(dir.path().join("share/doc")).unwrap();
+        fs::write(dir.path().join("bin/tool"), "#!/bin/sh\necho hi\n").unwrap();
+        fs::write(dir.path().join("share/doc/README"), "docs").unwrap();
+        dir
+    }
+
+    #[test]
+    fn packing_is_reproducible() {
+        let out = tempfile::tempdir().unwrap();
+        let first = pack(source().path(), &out.path().join("a"), &options()).unwrap();
+        let second = pack(source().path(), &out.path().join("b"), &options()).unwrap();
+
+        assert_eq!(
+            fs::read(&first.archive).unwrap(),
+            fs::read(&second.archive).unwrap()
+        );
+        assert_eq!(
+            first.package.install.url,
+            "https://example.com/releases/1.2.0/tool.jellyfish"
+        );
+        let written = fs::read_to_string(&first.package_toml).unwrap();
+        assert_eq!(
+            Package::from_toml(&written, first.package_toml).unwrap(),
+            first.package
+        );
+
+        let extracted = out.path().join("extracted");
+        let archive = GzDecoder::new(File::open(&first.archive).unwrap());
+        unpack_tar(archive, &extracted, ExtractLimits::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(extracted.join("share/doc/README")).unwrap(),
+            "docs"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(extracted.join("bin/tool"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[test]
+    fn packing_into_the_source_skips_previous_output() {
+        let dir = source();
+        let first = pack(dir.path(), dir.path(), &options()).unwrap();
+        let second = pack(dir.path(), dir.path(), &options()).unwrap();
+        assert_eq!(first.package, second.package);
+
+        let nested = pack(dir.path(), &dir.path().join("dist"), &options()).unwrap();
+        let again = pack(dir.path(), &dir.path().join("dist"), &options()).unwrap();
+        assert_eq!(nested.package, again.package);
+
+        let archive = GzDecoder::new(File::open(&again.archive).unwrap());
+        let mut paths: Vec<String> = tar::Archive::new(archive)
+            .entries()
+            .unwrap()
+            .map(|x| x.unwrap().path().unwrap().display().to_string())
+            .collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "bin",
+                "bin/tool",
+                "dist",
+                "package.toml",
+                "share",
+                "share/doc",
+                "share/doc/README",
+                "tool.jellyfish"
+            ]
+        );
+    }
+
+    #[test]
+    fn requires_bin_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = pack(dir.path(), &dir.path().join("out"), &options()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("'{}' has no bin folder", dir.path().display())
+        );
+    }
+}
@@ -1,25 +1,309 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use failure::Fail;
+use reqwest::Url;
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 
+/// Newest package.toml schema this version of voran understands.
+pub const SCHEMA_VERSION: u32 = 1;
+
 /// Package information
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Package {
+    /// Version of the package.toml format, packages written before it existed are version 1.
+    #[serde(default = "default_schema")]
+    pub schema: u32,
     pub name: String,
     pub friendly_name: String,
     pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub homepage: Option<String>,
+    /// Source code repository of the package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// SPDX license expression, eg. "MIT OR Apache-2.0".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// Oldest version of voran able to install the package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_voran_version: Option<String>,
     pub install: InstallInfo,
+    /// Packages this package needs, as name -> semver requirement (eg. "^1.2").
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dependencies: BTreeMap<String, String>,
 }
 
 /// Package install information
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct InstallInfo {
     pub url: String,
+    /// Urls serving the same file, tried in order if url fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
     pub type_: PackageType,
+    /// Archive format of the file at url, detected from its contents if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ArchiveFormat>,
+    /// Hex encoded sha256 checksum of the file at url.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+fn default_schema() -> u32 {
+    1
+}
+
+/// Errors returned when a package.toml can't be loaded.
+#[derive(Debug)]
+pub enum ManifestError {
+    /// The file does not exist.
+    Missing { path: PathBuf },
+    /// The file is not valid TOML or is missing required fields.
+    Invalid { path: PathBuf, message: String },
+    /// The file was written for a newer version of voran.
+    UnsupportedSchema { path: PathBuf, schema: u32 },
+    /// A field has a value that isn't allowed.
+    InvalidField {
+        path: PathBuf,
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Missing { path } => write!(f, "{} not found", path.display()),
+            ManifestError::Invalid { path, message } => {
+                write!(f, "{} is invalid: {}", path.display(), message)
+            }
+            ManifestError::UnsupportedSchema { path, schema } => write!(
+                f,
+                "{} uses schema {}, but this version of voran only supports up to {}",
+                path.display(),
+                schema,
+                SCHEMA_VERSION
+            ),
+            ManifestError::InvalidField {
+                path,
+                field,
+                message,
+            } => write!(f, "{}: '{}' {}", path.display(), field, message),
+        }
+    }
+}
+
+impl Fail for ManifestError {}
+
+impl Package {
+    /// Parse a package.toml, checking its schema version and field values.
+    /// path is only used in errors.
+    pub fn from_toml(contents: &str, path: PathBuf) -> Result<Package, ManifestError> {
+        let package: Package = toml::from_str(contents).map_err(|err| ManifestError::Invalid {
+            path: path.clone(),
+            message: err.to_string(),
+        })?;
+        if package.schema > SCHEMA_VERSION {
+            return Err(ManifestError::UnsupportedSchema {
+                path,
+                schema: package.schema,
+            });
+        }
+        package.validate(path)?;
+        Ok(package)
+    }
+
+    /// Check that every field holds a usable value.
+    pub fn validate(&self, path: PathBuf) -> Result<(), ManifestError> {
+        let invalid = |field: &str, message: String| ManifestError::InvalidField {
+            path: path.clone(),
+            field: field.to_string(),
+            message,
+        };
+
+        if !is_folder_name(&self.name) {
+            return Err(invalid("name", "is not a valid package name".to_string()));
+        }
+        // Versions name a folder in the repository, eg. `<name>/<version>/package.toml`
+        if !is_folder_name(&self.version) {
+            return Err(invalid(
+                "version",
+                "is not a valid version folder name".to_string(),
+            ));
+        }
+
+        let urls = [
+            ("install.url", Some(&self.install.url)),
+            ("homepage", self.homepage.as_ref()),
+            ("repository", self.repository.as_ref()),
+        ];
+        let mirrors = self
+            .install
+            .mirrors
+            .iter()
+            .map(|x| ("install.mirrors", Some(x)));
+        for (field, url) in urls.into_iter().chain(mirrors) {
+            if let Some(url) = url {
+                if let Err(err) = Url::parse(url) {
+                    return Err(invalid(field, format!("is not a valid url ({})", err)));
+                }
+            }
+        }
+
+        if let Some(sha256) = &self.install.sha256 {
+            if sha256.len() != 64 || !sha256.chars().all(|x| x.is_ascii_hexdigit()) {
+                return Err(invalid(
+                    "install.sha256",
+                    "must be 64 hexadecimal characters".to_string(),
+                ));
+            }
+        }
+
+        for (name, requirement) in &self.dependencies {
+            if VersionReq::parse(requirement).is_err() {
+                return Err(invalid(
+                    &format!("dependencies.{}", name),
+                    format!("'{}' is not a valid version requirement", requirement),
+                ));
+            }
+        }
+
+        if let Some(min) = &self.min_voran_version {
+            if let Err(err) = Version::parse(min) {
+                return Err(invalid(
+                    "min_voran_version",
+                    format!("is not a valid version ({})", err),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that this version of voran is new enough to install the package.
+    /// Packages needing a newer voran still load, so they can be listed and searched.
+    pub fn check_voran_version(&self) -> Result<(), failure::Error> {
+        let min = match self.min_voran_version.as_deref().map(Version::parse) {
+            Some(min) => min?,
+            None => return Ok(()),
+        };
+        let current = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
+        if min > current {
+            return Err(failure::err_msg(format!(
+                "{} v{} requires voran v{}, this is v{}",
+                self.name, self.version, min, current
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Whether a name can safely be used as a single folder name (eg. package, version or remote names).
+pub fn is_folder_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+impl InstallInfo {
+    /// Every url the file can be downloaded from, in the order they should be tried.
+    pub fn urls(&self) -> Vec<String> {
+        let mut urls = vec![self.url.clone()];
+        urls.extend(self.mirrors.iter().cloned());
+        urls
+    }
 }
 
 /// Package install type
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum PackageType {
     Executable,
     JellyFish,
     Wharf,
 }
+
+/// Archive format of a JellyFish or Wharf package
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ArchiveFormat {
+    TarGz,
+    TarXz,
+    TarZst,
+    Tar,
+    Zip,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGE: &str = r#"
+name = "tool"
+friendly_name = "Tool"
+version = "1.0.0"
+homepage = "https://example.com"
+license = "MIT"
+authors = ["Someone"]
+
+[install]
+url = "https://example.com/tool.tar.gz"
+type_ = "JellyFish"
+"#;
+
+    fn load(contents: &str) -> Result<Package, ManifestError> {
+        Package::from_toml(contents, PathBuf::from("tool/1.0.0/package.toml"))
+    }
+
+    #[test]
+    fn loads_metadata_with_default_schema() {
+        let package = load(PACKAGE).unwrap();
+        assert_eq!(package.schema, 1);
+        assert_eq!(package.license.as_deref(), Some("MIT"));
+        assert_eq!(package.authors, vec!["Someone"]);
+    }
+
+    #[test]
+    fn errors_name_file_and_field() {
+        let cases = vec![
+            (
+                format!("schema = 2\n{}", PACKAGE),
+                "tool/1.0.0/package.toml uses schema 2, but this version of voran only supports up to 1".to_string(),
+            ),
+            (
+                PACKAGE.replace("https://example.com\"", "example.com\""),
+                "tool/1.0.0/package.toml: 'homepage' is not a valid url (relative URL without a base)".to_string(),
+            ),
+            (
+                PACKAGE.replace("\"1.0.0\"", "\"1.0.0/../../..\""),
+                "tool/1.0.0/package.toml: 'version' is not a valid version folder name".to_string(),
+            ),
+            (
+                format!("min_voran_version = \"soon\"\n{}", PACKAGE),
+                "tool/1.0.0/package.toml: 'min_voran_version' is not a valid version (unexpected character 's' while parsing major version number)".to_string(),
+            ),
+            (
+                format!("{}\n[dependencies]\nother = \"not a version\"\n", PACKAGE),
+                "tool/1.0.0/package.toml: 'dependencies.other' 'not a version' is not a valid version requirement".to_string(),
+            ),
+        ];
+        for (contents, message) in cases {
+            assert_eq!(load(&contents).unwrap_err().to_string(), message);
+        }
+
+        // Packages for a newer voran load, but can't be installed
+        let package = load(&format!("min_voran_version = \"999.0.0\"\n{}", PACKAGE)).unwrap();
+        assert_eq!(
+            package.check_voran_version().unwrap_err().to_string(),
+            format!(
+                "tool v1.0.0 requires voran v999.0.0, this is v{}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        let err = load("name = \"tool\"").unwrap_err();
+        assert!(matches!(err, ManifestError::Invalid { .. }));
+    }
+}
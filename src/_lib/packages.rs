@@ -4,13 +4,77 @@ use std::{
 };
 
 use git_rs::GitRepository;
+use semver::{Version, VersionReq};
 
-use crate::{package::Package, proj_dirs};
+use crate::{
+    index,
+    manifest::Manifest,
+    package::{is_folder_name, ManifestError, Package},
+    proj_dirs, signature, Config,
+};
 
-/// Create a GetPackages at the package repository root.
-pub fn get_packages() -> GetPackages {
-    GetPackages {
-        dir: proj_dirs().data_local_dir().to_path_buf(),
+/// Folder every remote is checked out into.
+pub fn remotes_dir() -> PathBuf {
+    proj_dirs().data_local_dir().join("remotes")
+}
+
+/// Folder a remote is checked out into.
+pub fn remote_dir(name: &str) -> PathBuf {
+    remotes_dir().join(name)
+}
+
+/// Folder the packages of a remote are read from.
+pub fn remote_packages_dir(name: &str, url: &str) -> PathBuf {
+    match RemoteKind::from_url(url) {
+        RemoteKind::Local(dir) => dir,
+        RemoteKind::Git(_) | RemoteKind::Index(_) => remote_dir(name),
+    }
+}
+
+/// Packages of every configured remote, highest priority first.
+pub fn remote_packages(config: &Config) -> RemotePackages {
+    RemotePackages {
+        remotes: config
+            .remotes()
+            .into_iter()
+            // Checkouts of these would end up outside the remotes folder
+            .filter(|name| is_folder_name(name))
+            .map(|name| {
+                let dir = remote_packages_dir(&name, config.remote_url(&name).unwrap_or_default());
+                (name, dir)
+            })
+            .collect(),
+    }
+}
+
+/// Where the packages of a remote come from, told apart by the prefix of its url.
+#[derive(Debug, PartialEq)]
+pub enum RemoteKind {
+    /// A git repository, cloned into the remote's folder.
+    Git(String),
+    /// A static HTTP index (`sparse+<url>`), synced into the remote's folder.
+    Index(String),
+    /// A local folder (`dir+<path>`), read in place.
+    Local(PathBuf),
+}
+
+impl RemoteKind {
+    pub fn from_url(url: &str) -> Self {
+        if let Some(url) = url.strip_prefix("sparse+") {
+            RemoteKind::Index(url.to_string())
+        } else if let Some(path) = url.strip_prefix("dir+") {
+            RemoteKind::Local(PathBuf::from(path))
+        } else {
+            RemoteKind::Git(url.to_string())
+        }
+    }
+}
+
+/// Split a package name into its remote (if given) and bare name, eg. `main/ripgrep`.
+pub fn split_name(name: &str) -> (Option<&str>, &str) {
+    match name.split_once('/') {
+        Some((remote, name)) => (Some(remote), name),
+        None => (None, name),
     }
 }
 
@@ -53,6 +117,21 @@ impl GetPackages {
 pub trait Packages {
     /// Get the desired package.
     fn get_package(&self, name: &str) -> Option<GetPackage>;
+    /// Names of every package.
+    fn names(&self) -> Vec<String>;
+}
+
+/// Names of the package folders in a folder.
+fn folder_names(dir: &Path) -> Vec<String> {
+    match dir.read_dir() {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .filter(|name| !name.starts_with('.'))
+            .collect(),
+        Err(_) => vec![],
+    }
 }
 
 /// A Packages implementation that lazy loads the packages (ie. on command).
@@ -67,7 +146,81 @@ impl Packages for LazyPackages {
         if !path.exists() {
             return None;
         }
-        Some(GetPackage { dir: path })
+        Some(GetPackage {
+            dir: path,
+            remote: None,
+        })
+    }
+
+    fn names(&self) -> Vec<String> {
+        folder_names(&self.dir)
+    }
+}
+
+/// A Packages implementation over the checkouts of several remotes.
+///
+/// Packages can be addressed as `remote/name`, bare names are looked up in priority order.
+pub struct RemotePackages {
+    /// (remote name, checkout folder), highest priority first.
+    pub remotes: Vec<(String, PathBuf)>,
+}
+
+impl RemotePackages {
+    /// Every package of every remote, including those hidden by a higher priority remote.
+    pub async fn load(&self) -> Result<Vec<GetPackage>, failure::Error> {
+        let mut packages = vec![];
+        for (remote, dir) in &self.remotes {
+            // Not pulled yet
+            if !dir.is_dir() {
+                continue;
+            }
+            for mut get_package in GetPackages::new(dir).load().await? {
+                get_package.remote = Some(remote.clone());
+                packages.push(get_package);
+            }
+        }
+        Ok(packages)
+    }
+
+    /// Every package of every remote at its highest version, like load followed by latest.
+    /// The package.toml files of index remotes are downloaded together rather than one at a time.
+    pub async fn load_latest(&self) -> Result<Vec<GetPackage>, failure::Error> {
+        let mut packages = vec![];
+        for mut get_package in self.load().await? {
+            if get_package.latest().is_some() {
+                packages.push(get_package);
+            }
+        }
+        let dirs: Vec<PathBuf> = packages.iter().map(|x| x.dir.clone()).collect();
+        index::prefetch(&dirs).await;
+        Ok(packages)
+    }
+}
+
+impl Packages for RemotePackages {
+    /// Get the desired package, `remote/name` or the highest priority remote's `name`.
+    fn get_package(&self, name: &str) -> Option<GetPackage> {
+        let (remote, name) = split_name(name);
+        self.remotes
+            .iter()
+            .filter(|(x, _)| remote.is_none() || remote == Some(x.as_str()))
+            .find_map(|(x, dir)| {
+                let mut get_package = LazyPackages { dir: dir.clone() }.get_package(name)?;
+                get_package.remote = Some(x.clone());
+                Some(get_package)
+            })
+    }
+
+    /// Bare names of the packages of every remote, without duplicates.
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .remotes
+            .iter()
+            .flat_map(|(_, dir)| folder_names(dir))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
     }
 }
 
@@ -85,8 +238,17 @@ impl Packages for LoadPackages {
             .find(|x| x.file_name().unwrap_or_default().to_str().unwrap() == name && x.is_dir())?;
         Some(GetPackage {
             dir: path.to_path_buf(),
+            remote: None,
         })
     }
+
+    fn names(&self) -> Vec<String> {
+        self.packages
+            .iter()
+            .filter(|x| x.is_dir())
+            .filter_map(|x| x.file_name()?.to_str().map(str::to_string))
+            .collect()
+    }
 }
 
 impl LoadPackages {
@@ -115,7 +277,10 @@ impl Iterator for LoadPackages {
 
     fn next(&mut self) -> Option<Self::Item> {
         let path = self.packages.get(self.index)?.to_path_buf();
-        let out = Some(GetPackage { dir: path });
+        let out = Some(GetPackage {
+            dir: path,
+            remote: None,
+        });
         self.index += 1;
         out
     }
@@ -124,9 +289,22 @@ impl Iterator for LoadPackages {
 /// Helper class to get package information from folder
 pub struct GetPackage {
     pub dir: PathBuf,
+    /// Remote the package comes from, None for installed packages and plain folders.
+    pub remote: Option<String>,
 }
 
 impl GetPackage {
+    /// Names of the version folders of this package, semver versions from lowest to highest
+    /// followed by any other folders (eg. LATEST) in name order.
+    pub fn versions(&self) -> Vec<String> {
+        let mut versions = folder_names(&self.dir);
+        versions.sort_by_cached_key(|x| {
+            let version = Version::parse(x).ok();
+            (version.is_none(), version, x.clone())
+        });
+        versions
+    }
+
     /// Navigate into the folder of a specific version
     pub fn version(&mut self, version: &str) -> Option<&mut Self> {
         let path = self.dir.join(version);
@@ -137,12 +315,126 @@ impl GetPackage {
         Some(self)
     }
 
-    /// Load package.toml
-    pub fn package(&self) -> Option<Package> {
+    /// Name of the highest semver version folder matching a requirement.
+    pub fn highest_matching(&self, requirement: &VersionReq) -> Option<String> {
+        self.versions()
+            .into_iter()
+            .filter_map(|x| Version::parse(&x).ok().map(|version| (version, x)))
+            .filter(|(version, _)| requirement.matches(version))
+            .max()
+            .map(|(_, x)| x)
+    }
+
+    /// Navigate into the folder of the highest version.
+    /// Repositories without semver version folders fall back to LATEST.
+    pub fn latest(&mut self) -> Option<&mut Self> {
+        match self.highest_matching(&VersionReq::STAR) {
+            Some(version) => self.version(&version),
+            None => self.version("LATEST"),
+        }
+    }
+
+    /// Navigate into the folder of a version folder name or the highest version matching a
+    /// requirement (eg. "^1.2"), or the highest version if None.
+    pub fn select_version(&mut self, version: Option<&str>) -> Option<&mut Self> {
+        let version = match version {
+            None => return self.latest(),
+            Some(version) => version,
+        };
+        if self.dir.join(version).is_dir() {
+            return self.version(version);
+        }
+        let requirement = VersionReq::parse(version).ok()?;
+        let version = self.highest_matching(&requirement)?;
+        self.version(&version)
+    }
+
+    /// Load package.toml, failing with a ManifestError naming the file if it is missing or invalid.
+    pub fn package(&self) -> Result<Package, failure::Error> {
+        index::fetch_missing(&self.dir)?;
         let path = self.dir.join("package.toml");
         if !path.exists() {
-            return None;
+            return Err(ManifestError::Missing { path }.into());
         }
-        Some(toml::from_str(fs::read_to_string(path).unwrap().as_str()).unwrap())
+        let contents = fs::read_to_string(&path)?;
+        Ok(Package::from_toml(&contents, path)?)
+    }
+
+    /// Load the manifest of files and links owned by an installed package.
+    pub fn files(&self) -> Result<Manifest, failure::Error> {
+        Manifest::load(&self.dir)
+    }
+
+    /// Load package.toml after checking its signature (package.toml.minisig) against trusted keys.
+    /// If no keys are given the signature is not checked.
+    pub fn verified_package(&self, keys: &[String]) -> Result<Package, failure::Error> {
+        index::fetch_missing(&self.dir)?;
+        let path = self.dir.join("package.toml");
+        if !keys.is_empty() && path.exists() {
+            signature::verify_file(&path, keys)?;
+        }
+        self.package()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn remote_packages_are_namespaced() {
+        let dir = tempfile::tempdir().unwrap();
+        for remote in ["main", "extra"] {
+            fs::create_dir_all(dir.path().join(remote).join("tool/1.0.0")).unwrap();
+        }
+        fs::create_dir_all(dir.path().join("extra/other/1.0.0")).unwrap();
+        let packages = RemotePackages {
+            remotes: ["main", "extra"]
+                .iter()
+                .map(|x| (x.to_string(), dir.path().join(x)))
+                .collect(),
+        };
+
+        let remote = |name: &str| packages.get_package(name).and_then(|x| x.remote);
+        assert_eq!(remote("tool").as_deref(), Some("main"));
+        assert_eq!(remote("extra/tool").as_deref(), Some("extra"));
+        assert_eq!(remote("other").as_deref(), Some("extra"));
+        assert_eq!(remote("main/other"), None);
+        assert_eq!(packages.load().await.unwrap().len(), 3);
+    }
+
+    #[test]
+    fn versions_are_sorted_by_semver() {
+        let dir = tempfile::tempdir().unwrap();
+        for version in ["1.10.0", "LATEST", "1.2.0", "1.9.0", "1.2.0-beta.1"] {
+            fs::create_dir_all(dir.path().join("tool").join(version)).unwrap();
+        }
+        let get_package = LazyPackages {
+            dir: dir.path().to_path_buf(),
+        }
+        .get_package("tool")
+        .unwrap();
+
+        assert_eq!(
+            get_package.versions(),
+            vec!["1.2.0-beta.1", "1.2.0", "1.9.0", "1.10.0", "LATEST"]
+        );
+    }
+
+    #[test]
+    fn local_remotes_are_read_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("tool/1.0.0")).unwrap();
+        let config = Config {
+            git_repo_urls: vec![("usb".to_string(), format!("dir+{}", dir.path().display()))],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            RemoteKind::from_url("sparse+https://example.com/packages/"),
+            RemoteKind::Index("https://example.com/packages/".to_string())
+        );
+        let get_package = remote_packages(&config).get_package("usb/tool").unwrap();
+        assert_eq!(get_package.dir, dir.path().join("tool"));
     }
 }
@@ -0,0 +1,153 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use semver::VersionReq;
+
+use crate::{git, package::Package, packages::GetPackage, signature};
+
+/// A package version laid out in a repository by publish.
+#[derive(Debug)]
+pub struct Published {
+    pub package: Package,
+    /// The new version folder, relative to the repository root.
+    pub dir: PathBuf,
+    /// LATEST now points at this version.
+    pub latest: bool,
+}
+
+/// Copy a package.toml (and its signature, if any) into `<name>/<version>` of a repository,
+/// updating LATEST if it is the highest version.
+///
+/// Fails if the version was already published, unless force is set.
+pub fn publish(package_toml: &Path, repo: &Path, force: bool) -> Result<Published, failure::Error> {
+    let package = Package::from_toml(
+        &fs::read_to_string(package_toml)?,
+        package_toml.to_path_buf(),
+    )?;
+
+    let relative = Path::new(&package.name).join(&package.version);
+    let dir = repo.join(&relative);
+    if dir.exists() {
+        if !force {
+            return Err(failure::err_msg(format!(
+                "{} v{} is already published, use --force to overwrite it",
+                package.name, package.version
+            )));
+        }
+        fs::remove_dir_all(&dir)?;
+    }
+    copy_package(package_toml, &dir)?;
+
+    // Repositories without semver versions always point LATEST at the newest publish.
+    let highest = GetPackage {
+        dir: repo.join(&package.name),
+        remote: None,
+    }
+    .highest_matching(&VersionReq::STAR);
+    let latest = highest.is_none() || highest.as_deref() == Some(package.version.as_str());
+    if latest {
+        let latest_dir = repo.join(&package.name).join("LATEST");
+        if latest_dir.exists() {
+            fs::remove_dir_all(&latest_dir)?;
+        }
+        copy_package(package_toml, &latest_dir)?;
+    }
+
+    Ok(Published {
+        package,
+        dir: relative,
+        latest,
+    })
+}
+
+/// Commit a published package to the repository.
+pub fn commit(repo: &Path, published: &Published) -> Result<(), failure::Error> {
+    git::run(repo, &["add", "--", &published.package.name])?;
+    git::run(
+        repo,
+        &[
+            "commit",
+            "-m",
+            &format!(
+                "Publish {} v{}",
+                published.package.name, published.package.version
+            ),
+        ],
+    )?;
+    Ok(())
+}
+
+fn copy_package(package_toml: &Path, dir: &Path) -> Result<(), failure::Error> {
+    fs::create_dir_all(dir)?;
+    let dest = dir.join("package.toml");
+    fs::copy(package_toml, &dest)?;
+    let signature = signature::signature_path(package_toml);
+    if signature.exists() {
+        fs::copy(signature, signature::signature_path(&dest))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn package_toml(dir: &Path, version: &str) -> PathBuf {
+        testing::write_package(&dir.join(version), &testing::package("tool", version))
+    }
+
+    fn latest_version(repo: &Path) -> String {
+        GetPackage {
+            dir: repo.join("tool/LATEST"),
+            remote: None,
+        }
+        .package()
+        .unwrap()
+        .version
+    }
+
+    #[test]
+    fn publishes_versions_and_moves_latest() {
+        let source = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+
+        let published = publish(&package_toml(source.path(), "1.1.0"), repo.path(), false).unwrap();
+        assert_eq!(published.dir, Path::new("tool").join("1.1.0"));
+        assert!(published.latest);
+        assert!(repo.path().join("tool/1.1.0/package.toml").exists());
+
+        // Older versions don't move LATEST
+        let published = publish(&package_toml(source.path(), "1.0.0"), repo.path(), false).unwrap();
+        assert!(!published.latest);
+        assert_eq!(latest_version(repo.path()), "1.1.0");
+
+        let err = publish(&package_toml(source.path(), "1.0.0"), repo.path(), false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "tool v1.0.0 is already published, use --force to overwrite it"
+        );
+        publish(&package_toml(source.path(), "1.0.0"), repo.path(), true).unwrap();
+    }
+
+    #[test]
+    fn commit_reports_git_failures() {
+        let source = tempfile::tempdir().unwrap();
+        let repo = tempfile::tempdir().unwrap();
+        let published = publish(&package_toml(source.path(), "1.0.0"), repo.path(), false).unwrap();
+
+        // Not a git repository yet
+        assert!(commit(repo.path(), &published).is_err());
+
+        git::run(repo.path(), &["init", "-q"]).unwrap();
+        git::run(repo.path(), &["config", "user.name", "voran"]).unwrap();
+        git::run(repo.path(), &["config", "user.email", "voran@example.com"]).unwrap();
+        commit(repo.path(), &published).unwrap();
+        assert_eq!(
+            git::run(repo.path(), &["log", "--format=%s"]).unwrap(),
+            "Publish tool v1.0.0"
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use futures_util::future;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    git, index,
+    package::is_folder_name,
+    packages::{self, RemoteKind},
+    Config,
+};
+
+/// File in the remotes folder recording what each remote was last synced from.
+const STATE_FILE: &str = ".state.toml";
+
+/// What syncing a remote did.
+#[derive(Debug, PartialEq)]
+pub enum SyncAction {
+    /// The remote was synced for the first time, or its url or ref changed.
+    Added,
+    /// The remote was brought up to date.
+    Updated,
+    /// The remote is no longer configured and its checkout was deleted.
+    Removed,
+}
+
+/// The outcome of syncing one remote.
+#[derive(Debug)]
+pub struct RemoteSync {
+    pub remote: String,
+    pub result: Result<SyncAction, failure::Error>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SyncState {
+    remotes: Vec<SyncedRemote>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+struct SyncedRemote {
+    name: String,
+    url: String,
+    reference: Option<String>,
+}
+
+/// Bring every remote's checkout in line with the configuration.
+///
+/// New remotes are added, removed ones deleted and the rest fetched, all in parallel.
+/// A remote failing doesn't stop the others, and its previous checkout is kept.
+pub async fn sync(config: &Config) -> Result<Vec<RemoteSync>, failure::Error> {
+    sync_in(config, &packages::remotes_dir()).await
+}
+
+async fn sync_in(config: &Config, dir: &Path) -> Result<Vec<RemoteSync>, failure::Error> {
+    fs::create_dir_all(dir)?;
+    let state_path = dir.join(STATE_FILE);
+    let mut state: SyncState = match fs::read_to_string(&state_path) {
+        Ok(contents) => toml::from_str(&contents).unwrap_or_default(),
+        Err(_) => SyncState::default(),
+    };
+
+    let mut results = vec![];
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Local remotes are read in place and have no checkout
+        let configured = matches!(
+            config.remote_url(&name).map(RemoteKind::from_url),
+            Some(RemoteKind::Git(_) | RemoteKind::Index(_))
+        );
+        if entry.file_type()?.is_dir() && !configured {
+            results.push(RemoteSync {
+                result: fs::remove_dir_all(entry.path())
+                    .map(|_| SyncAction::Removed)
+                    .map_err(Into::into),
+                remote: name,
+            });
+        }
+    }
+    state
+        .remotes
+        .retain(|x| config.remote_url(&x.name).is_some());
+
+    // Names edited into the config by hand could point the checkout outside dir
+    let (valid, invalid): (Vec<_>, Vec<_>) = config
+        .git_repo_urls
+        .iter()
+        .partition(|(name, _)| is_folder_name(name));
+    for (name, _) in invalid {
+        results.push(RemoteSync {
+            remote: name.clone(),
+            result: Err(failure::err_msg(format!(
+                "'{}' is not a valid remote name",
+                name
+            ))),
+        });
+    }
+
+    let synced = future::join_all(valid.into_iter().map(|(name, url)| {
+        let remote = SyncedRemote {
+            name: name.clone(),
+            url: url.clone(),
+            reference: config.remote_ref(name).map(str::to_string),
+        };
+        let previous = state.remotes.iter().find(|x| &x.name == name).cloned();
+        let dir = dir.join(name);
+        async move {
+            let result = sync_remote(&remote, previous.as_ref(), dir).await;
+            (remote, result)
+        }
+    }))
+    .await;
+
+    for (remote, result) in synced {
+        if result.is_ok() {
+            state.remotes.retain(|x| x.name != remote.name);
+            state.remotes.push(remote.clone());
+        }
+        results.push(RemoteSync {
+            remote: remote.name,
+            result,
+        });
+    }
+
+    fs::write(state_path, toml::to_string(&state)?)?;
+    Ok(results)
+}
+
+async fn sync_remote(
+    remote: &SyncedRemote,
+    previous: Option<&SyncedRemote>,
+    dir: PathBuf,
+) -> Result<SyncAction, failure::Error> {
+    let action = match previous {
+        Some(previous) if previous == remote && (dir.exists() || is_local(remote)) => {
+            SyncAction::Updated
+        }
+        _ => SyncAction::Added,
+    };
+
+    match RemoteKind::from_url(&remote.url) {
+        RemoteKind::Index(url) => index::sync(&url, &dir).await?,
+        RemoteKind::Local(path) => {
+            if !path.is_dir() {
+                return Err(failure::err_msg(format!(
+                    "'{}' is not a folder",
+                    path.display()
+                )));
+            }
+        }
+        RemoteKind::Git(url) => {
+            let remote = remote.clone();
+            let add = action == SyncAction::Added;
+            // git blocks, so each repository gets its own thread.
+            tokio::task::spawn_blocking(move || sync_git(&remote, &url, &dir, add)).await??;
+        }
+    }
+    Ok(action)
+}
+
+/// Fetch a git remote and check out what it points at.
+fn sync_git(remote: &SyncedRemote, url: &str, dir: &Path, add: bool) -> Result<(), failure::Error> {
+    fs::create_dir_all(dir)?;
+    if !dir.join(".git").exists() {
+        git::run(dir, &["init", "-q"])?;
+    }
+    if add {
+        if git::run(dir, &["remote"])?
+            .lines()
+            .any(|x| x == remote.name)
+        {
+            git::run(dir, &["remote", "remove", &remote.name])?;
+        }
+        git::run(dir, &["remote", "add", &remote.name, url])?;
+    }
+    git::run(dir, &["fetch", "-q", "--tags", &remote.name])?;
+    let target = match &remote.reference {
+        Some(reference) => {
+            // Branches are followed through the remote, a local branch would stay where it was
+            // first checked out.
+            let branch = format!("{}/{}", remote.name, reference);
+            let is_branch = git::run(
+                dir,
+                &[
+                    "rev-parse",
+                    "-q",
+                    "--verify",
+                    &format!("refs/remotes/{}", branch),
+                ],
+            )
+            .is_ok();
+            if is_branch {
+                branch
+            } else {
+                reference.clone()
+            }
+        }
+        None => {
+            git::run(dir, &["remote", "set-head", &remote.name, "--auto"])?;
+            format!("{}/HEAD", remote.name)
+        }
+    };
+    git::run(dir, &["checkout", "-q", "--detach", &target])?;
+    Ok(())
+}
+
+fn is_local(remote: &SyncedRemote) -> bool {
+    matches!(RemoteKind::from_url(&remote.url), RemoteKind::Local(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A git repository with one commit holding tool/<version>.
+    fn source_repo(dir: &Path) {
+        git::run(dir, &["init", "-q"]).unwrap();
+        git::run(dir, &["config", "user.name", "voran"]).unwrap();
+        git::run(dir, &["config", "user.email", "voran@example.com"]).unwrap();
+        commit_version(dir, "1.0.0");
+    }
+
+    fn commit_version(dir: &Path, version: &str) {
+        fs::create_dir_all(dir.join("tool").join(version)).unwrap();
+        fs::write(dir.join("tool").join(version).join("package.toml"), "").unwrap();
+        git::run(dir, &["add", "."]).unwrap();
+        git::run(dir, &["commit", "-q", "-m", version]).unwrap();
+    }
+
+    fn actions(results: &[RemoteSync]) -> Vec<(&str, Option<&SyncAction>)> {
+        let mut results: Vec<_> = results
+            .iter()
+            .map(|x| (x.remote.as_str(), x.result.as_ref().ok()))
+            .collect();
+        results.sort_by_key(|x| x.0);
+        results
+    }
+
+    #[tokio::test]
+    async fn git_remotes_are_fetched_and_failures_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let remotes = dir.path().join("remotes");
+        let source = dir.path().join("source");
+        fs::create_dir_all(&source).unwrap();
+        source_repo(&source);
+
+        let config = Config {
+            git_repo_urls: vec![
+                ("main".to_string(), source.display().to_string()),
+                (
+                    "broken".to_string(),
+                    dir.path().join("missing").display().to_string(),
+                ),
+            ],
+            ..Default::default()
+        };
+        let results = sync_in(&config, &remotes).await.unwrap();
+        assert_eq!(
+            actions(&results),
+            vec![("broken", None), ("main", Some(&SyncAction::Added))]
+        );
+        assert!(remotes.join("main/tool/1.0.0/package.toml").exists());
+
+        commit_version(&source, "1.1.0");
+        let results = sync_in(&config, &remotes).await.unwrap();
+        assert_eq!(
+            actions(&results),
+            vec![("broken", None), ("main", Some(&SyncAction::Updated))]
+        );
+        assert!(remotes.join("main/tool/1.1.0/package.toml").exists());
+    }
+
+    #[tokio::test]
+    async fn pinned_branches_move_forward_and_unpinning_returns_to_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let remotes = dir.path().join("remotes");
+        let source = dir.path().join("source");
+        fs::create_dir_all(&source).unwrap();
+        source_repo(&source);
+        git::run(&source, &["tag", "v1"]).unwrap();
+        git::run(&source, &["checkout", "-q", "-b", "develop"]).unwrap();
+        commit_version(&source, "2.0.0-beta");
+
+        let mut config = Config {
+            git_repo_urls: vec![("main".to_string(), source.display().to_string())],
+            remote_refs: vec![("main".to_string(), "develop".to_string())],
+            ..Default::default()
+        };
+        let checkout = remotes.join("main/tool");
+        sync_in(&config, &remotes).await.unwrap();
+        assert!(checkout.join("2.0.0-beta").exists());
+
+        commit_version(&source, "2.0.0");
+        let results = sync_in(&config, &remotes).await.unwrap();
+        assert_eq!(
+            actions(&results),
+            vec![("main", Some(&SyncAction::Updated))]
+        );
+        assert!(checkout.join("2.0.0").exists());
+
+        config.remote_refs = vec![("main".to_string(), "v1".to_string())];
+        sync_in(&config, &remotes).await.unwrap();
+        assert!(!checkout.join("2.0.0-beta").exists());
+
+        // The default branch of source is whatever it was created with, not develop
+        git::run(&source, &["checkout", "-q", "-"]).unwrap();
+        commit_version(&source, "1.1.0");
+        config.remote_refs.clear();
+        sync_in(&config, &remotes).await.unwrap();
+        assert!(checkout.join("1.1.0").exists());
+        assert!(!checkout.join("2.0.0").exists());
+    }
+
+    #[tokio::test]
+    async fn remotes_are_reconciled() {
+        let dir = tempfile::tempdir().unwrap();
+        let remotes = dir.path().join("remotes");
+        let local = dir.path().join("local");
+        fs::create_dir_all(remotes.join("removed")).unwrap();
+        fs::create_dir_all(&local).unwrap();
+
+        let mut config = Config {
+            git_repo_urls: vec![
+                ("usb".to_string(), format!("dir+{}", local.display())),
+                (
+                    "missing".to_string(),
+                    format!("dir+{}", dir.path().join("missing").display()),
+                ),
+                (
+                    "..".to_string(),
+                    "sparse+https://example.com/packages/".to_string(),
+                ),
+            ],
+            ..Default::default()
+        };
+        let results = sync_in(&config, &remotes).await.unwrap();
+        assert_eq!(
+            actions(&results),
+            vec![
+                ("..", None),
+                ("missing", None),
+                ("removed", Some(&SyncAction::Removed)),
+                ("usb", Some(&SyncAction::Added)),
+            ]
+        );
+        assert!(!remotes.join("removed").exists());
+
+        assert!(remotes.exists());
+
+        config.git_repo_urls.truncate(1);
+        let results = sync_in(&config, &remotes).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].result.as_ref().ok(), Some(&SyncAction::Updated));
+    }
+}
@@ -0,0 +1,558 @@
+use std::fmt;
+
+use failure::Fail;
+use semver::{Version, VersionReq};
+
+use crate::{package::Package, packages::Packages, Config};
+
+/// Errors returned when the dependencies of a package can't be resolved.
+#[derive(Debug)]
+pub enum ResolveError {
+    /// A dependency does not exist in the repository.
+    NotFound { name: String, required_by: String },
+    /// A dependency has an unparsable version requirement.
+    InvalidRequirement {
+        name: String,
+        requirement: String,
+        required_by: String,
+    },
+    /// No version of a dependency satisfies every requirement on it.
+    Unsatisfiable {
+        name: String,
+        /// (required by, requirement) of every requirement on the dependency.
+        requirements: Vec<(String, String)>,
+    },
+    /// Packages depend on each other in a loop.
+    Cycle { packages: Vec<String> },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::NotFound { name, required_by } => write!(
+                f,
+                "'{}' requires '{}', which does not exist",
+                required_by, name
+            ),
+            ResolveError::InvalidRequirement {
+                name,
+                requirement,
+                required_by,
+            } => write!(
+                f,
+                "'{}' requires '{} {}', which is not a valid version requirement",
+                required_by, name, requirement
+            ),
+            ResolveError::Unsatisfiable { name, requirements } if requirements.len() == 1 => {
+                let (required_by, requirement) = &requirements[0];
+                write!(
+                    f,
+                    "'{}' requires '{} {}', but no version matches",
+                    required_by, name, requirement
+                )
+            }
+            ResolveError::Unsatisfiable { name, requirements } => {
+                let requirements: Vec<String> = requirements
+                    .iter()
+                    .map(|(required_by, requirement)| {
+                        format!("'{}' requires '{}'", required_by, requirement)
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "No version of '{}' matches every requirement: {}",
+                    name,
+                    requirements.join(", ")
+                )
+            }
+            ResolveError::Cycle { packages } => {
+                write!(f, "Dependency cycle: {}", packages.join(" -> "))
+            }
+        }
+    }
+}
+
+impl Fail for ResolveError {}
+
+/// A package chosen by the resolver.
+#[derive(Debug)]
+pub struct ResolvedPackage {
+    pub package: Package,
+    /// Remote the package comes from, None if it isn't known.
+    pub remote: Option<String>,
+    /// The package is already installed at this version and does not need installing.
+    pub installed: bool,
+}
+
+/// Resolve the dependency graph of a package.
+///
+/// Returns every package in install order (dependencies first, the package itself last).
+/// Every requirement on a dependency, including those of installed packages, is gathered before
+/// a version is chosen; if a choice turns out to conflict with a later requirement the next
+/// lower version is tried. Dependencies that are already installed with a matching version are
+/// reused, the others are checked against the trusted keys of the remote they come from.
+pub fn resolve(
+    available: &impl Packages,
+    installed: &impl Packages,
+    config: &Config,
+    package: Package,
+    remote: Option<String>,
+) -> Result<Vec<ResolvedPackage>, failure::Error> {
+    package.check_voran_version()?;
+    let package_requirements = requirements(&package)?;
+
+    // Installed packages keep working only if what they depend on stays compatible
+    let installed_requirements = installed
+        .names()
+        .iter()
+        .filter_map(|name| installed.get_package(name)?.package().ok())
+        .flat_map(|package| requirements(&package).unwrap_or_default())
+        .collect();
+
+    let mut resolver = Resolver {
+        available,
+        installed,
+        config,
+        installed_requirements,
+        selected: vec![(
+            ResolvedPackage {
+                package,
+                remote,
+                installed: false,
+            },
+            package_requirements,
+        )],
+    };
+    resolver.solve()?;
+    resolver.order()
+}
+
+/// Whether a version string satisfies a requirement.
+pub fn matches(requirement: &VersionReq, version: &str) -> bool {
+    Version::parse(version)
+        .map(|version| requirement.matches(&version))
+        .unwrap_or(false)
+}
+
+/// A version requirement of one package on another.
+struct Requirement {
+    name: String,
+    requirement: String,
+    req: VersionReq,
+    required_by: String,
+}
+
+/// Parse the dependencies of a package.
+fn requirements(package: &Package) -> Result<Vec<Requirement>, ResolveError> {
+    package
+        .dependencies
+        .iter()
+        .map(|(name, requirement)| {
+            let req =
+                VersionReq::parse(requirement).map_err(|_| ResolveError::InvalidRequirement {
+                    name: name.clone(),
+                    requirement: requirement.clone(),
+                    required_by: package.name.clone(),
+                })?;
+            Ok(Requirement {
+                name: name.clone(),
+                requirement: requirement.clone(),
+                req,
+                required_by: package.name.clone(),
+            })
+        })
+        .collect()
+}
+
+/// A version the resolver can try for a dependency.
+enum Candidate {
+    /// The installed version.
+    Installed,
+    /// A version folder of the available packages.
+    Available(String),
+}
+
+struct Resolver<'a, A: Packages, I: Packages> {
+    available: &'a A,
+    installed: &'a I,
+    config: &'a Config,
+    /// Dependencies of the installed packages.
+    installed_requirements: Vec<Requirement>,
+    /// Chosen packages and their dependencies, in the order they were chosen.
+    selected: Vec<(ResolvedPackage, Vec<Requirement>)>,
+}
+
+impl<'a, A: Packages, I: Packages> Resolver<'a, A, I> {
+    /// Choose a version for every dependency, trying lower versions when a choice conflicts.
+    fn solve(&mut self) -> Result<(), failure::Error> {
+        for (resolved, _) in &self.selected {
+            let name = &resolved.package.name;
+            let requirements = self.requirements_on(name);
+            if requirements
+                .iter()
+                .any(|x| !matches(&x.req, &resolved.package.version))
+            {
+                return Err(self.unsatisfiable(name).into());
+            }
+        }
+
+        let name = match self
+            .selected
+            .iter()
+            .flat_map(|(_, requirements)| requirements)
+            .find(|x| self.position(&x.name).is_none())
+        {
+            Some(requirement) => requirement.name.clone(),
+            None => return Ok(()),
+        };
+
+        let mut first_err = None;
+        for candidate in self.candidates(&name)? {
+            let selected = self.load(&name, candidate)?;
+            let err = match requirements(&selected.package) {
+                Ok(requirements) => {
+                    self.selected.push((selected, requirements));
+                    match self.solve() {
+                        Ok(()) => return Ok(()),
+                        Err(err) => err,
+                    }
+                }
+                Err(err) => err.into(),
+            };
+            // Only resolution failures are worth trying another version for
+            if err.downcast_ref::<ResolveError>().is_none() {
+                return Err(err);
+            }
+            self.selected.retain(|(x, _)| x.package.name != name);
+            first_err.get_or_insert(err);
+        }
+        Err(first_err.unwrap_or_else(|| self.unsatisfiable(&name).into()))
+    }
+
+    /// Versions of a dependency matching every requirement on it, the installed one first then
+    /// from highest to lowest.
+    fn candidates(&self, name: &str) -> Result<Vec<Candidate>, ResolveError> {
+        let requirements = self.requirements_on(name);
+        let matches_all = |version: &str| requirements.iter().all(|x| matches(&x.req, version));
+
+        let installed = self.installed.get_package(name);
+        let available = self.available.get_package(name);
+        if installed.is_none() && available.is_none() {
+            return Err(ResolveError::NotFound {
+                name: name.to_string(),
+                required_by: requirements[0].required_by.clone(),
+            });
+        }
+
+        let mut candidates = vec![];
+        if let Some(Ok(package)) = installed.map(|x| x.package()) {
+            if matches_all(&package.version) {
+                candidates.push(Candidate::Installed);
+            }
+        }
+        if let Some(available) = available {
+            let mut versions: Vec<(Version, String)> = available
+                .versions()
+                .into_iter()
+                .filter(|x| matches_all(x))
+                .filter_map(|x| Version::parse(&x).ok().map(|version| (version, x)))
+                .collect();
+            versions.sort();
+            candidates.extend(
+                versions
+                    .into_iter()
+                    .rev()
+                    .map(|(_, x)| Candidate::Available(x)),
+            );
+        }
+        Ok(candidates)
+    }
+
+    /// Load the package of a candidate, checking the signature of available ones.
+    fn load(&self, name: &str, candidate: Candidate) -> Result<ResolvedPackage, failure::Error> {
+        match candidate {
+            Candidate::Installed => {
+                let get_package = self.installed.get_package(name).unwrap();
+                Ok(ResolvedPackage {
+                    package: get_package.package()?,
+                    remote: get_package.files().ok().and_then(|x| x.remote),
+                    installed: true,
+                })
+            }
+            Candidate::Available(version) => {
+                let mut get_package = self.available.get_package(name).unwrap();
+                let keys = self.config.package_keys(get_package.remote.as_deref());
+                let package = get_package
+                    .version(&version)
+                    .unwrap()
+                    .verified_package(&keys)?;
+                package.check_voran_version()?;
+                Ok(ResolvedPackage {
+                    package,
+                    remote: get_package.remote,
+                    installed: false,
+                })
+            }
+        }
+    }
+
+    /// Every requirement on a package, from the chosen packages and from the installed packages
+    /// that aren't being replaced.
+    fn requirements_on(&self, name: &str) -> Vec<&Requirement> {
+        let chosen = self
+            .selected
+            .iter()
+            .flat_map(|(_, requirements)| requirements);
+        let installed = self
+            .installed_requirements
+            .iter()
+            .filter(|x| self.position(&x.required_by).is_none());
+        chosen.chain(installed).filter(|x| x.name == name).collect()
+    }
+
+    fn unsatisfiable(&self, name: &str) -> ResolveError {
+        ResolveError::Unsatisfiable {
+            name: name.to_string(),
+            requirements: self
+                .requirements_on(name)
+                .iter()
+                .map(|x| (x.required_by.clone(), x.requirement.clone()))
+                .collect(),
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.selected
+            .iter()
+            .position(|(x, _)| x.package.name == name)
+    }
+
+    /// The chosen packages in install order, dependencies first.
+    fn order(self) -> Result<Vec<ResolvedPackage>, failure::Error> {
+        let mut stack = vec![];
+        let mut order = vec![];
+        self.visit(0, &mut stack, &mut order)?;
+        let mut selected: Vec<_> = self.selected.into_iter().map(Some).collect();
+        Ok(order
+            .into_iter()
+            .filter_map(|x| selected[x].take().map(|(resolved, _)| resolved))
+            .collect())
+    }
+
+    fn visit(
+        &self,
+        index: usize,
+        stack: &mut Vec<String>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), ResolveError> {
+        let name = &self.selected[index].0.package.name;
+        if let Some(pos) = stack.iter().position(|x| x == name) {
+            let mut packages = stack[pos..].to_vec();
+            packages.push(name.clone());
+            return Err(ResolveError::Cycle { packages });
+        }
+        if order.contains(&index) {
+            return Ok(());
+        }
+
+        stack.push(name.clone());
+        for requirement in &self.selected[index].1 {
+            self.visit(self.position(&requirement.name).unwrap(), stack, order)?;
+        }
+        stack.pop();
+        order.push(index);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{packages::LazyPackages, testing};
+
+    fn package(name: &str, version: &str, dependencies: &[(&str, &str)]) -> Package {
+        Package {
+            dependencies: dependencies
+                .iter()
+                .map(|(name, req)| (name.to_string(), req.to_string()))
+                .collect(),
+            ..testing::package(name, version)
+        }
+    }
+
+    fn publish(repo: &Path, package: &Package) {
+        testing::write_package(&repo.join(&package.name).join(&package.version), package);
+    }
+
+    #[test]
+    fn resolves_highest_matching_versions_in_order() {
+        let repo = tempfile::tempdir().unwrap();
+        let installed = tempfile::tempdir().unwrap();
+        publish(repo.path(), &package("b", "1.0.0", &[]));
+        publish(repo.path(), &package("b", "1.2.0", &[("c", "*")]));
+        publish(repo.path(), &package("b", "2.0.0", &[]));
+        publish(repo.path(), &package("c", "0.1.0", &[]));
+
+        let available = LazyPackages {
+            dir: repo.path().to_path_buf(),
+        };
+        let installed = LazyPackages {
+            dir: installed.path().to_path_buf(),
+        };
+        let order = resolve(
+            &available,
+            &installed,
+            &Config::default(),
+            package("a", "1.0.0", &[("b", "^1"), ("c", "0.1")]),
+            None,
+        )
+        .unwrap();
+
+        let order: Vec<_> = order
+            .iter()
+            .map(|x| format!("{}@{}", x.package.name, x.package.version))
+            .collect();
+        assert_eq!(order, vec!["c@0.1.0", "b@1.2.0", "a@1.0.0"]);
+    }
+
+    fn resolve_names(
+        repo: &Path,
+        installed: &Path,
+        package: Package,
+    ) -> Result<Vec<String>, failure::Error> {
+        let available = LazyPackages {
+            dir: repo.to_path_buf(),
+        };
+        let installed = LazyPackages {
+            dir: installed.to_path_buf(),
+        };
+        let order = resolve(&available, &installed, &Config::default(), package, None)?;
+        Ok(order
+            .iter()
+            .map(|x| format!("{}@{}", x.package.name, x.package.version))
+            .collect())
+    }
+
+    #[test]
+    fn backtracks_to_a_version_matching_every_requirement() {
+        let repo = tempfile::tempdir().unwrap();
+        let installed = tempfile::tempdir().unwrap();
+        publish(repo.path(), &package("b", "1.0.0", &[]));
+        publish(repo.path(), &package("b", "1.2.0", &[]));
+        publish(repo.path(), &package("c", "1.0.0", &[("b", "=1.0.0")]));
+
+        let order = resolve_names(
+            repo.path(),
+            installed.path(),
+            package("a", "1.0.0", &[("b", "^1"), ("c", "*")]),
+        )
+        .unwrap();
+
+        assert_eq!(order, vec!["b@1.0.0", "c@1.0.0", "a@1.0.0"]);
+    }
+
+    #[test]
+    fn reports_unsatisfiable_requirements() {
+        let repo = tempfile::tempdir().unwrap();
+        let installed = tempfile::tempdir().unwrap();
+        publish(repo.path(), &package("b", "1.0.0", &[]));
+        publish(repo.path(), &package("b", "2.0.0", &[]));
+        publish(repo.path(), &package("c", "1.0.0", &[("b", "^2")]));
+
+        let err = resolve_names(
+            repo.path(),
+            installed.path(),
+            package("a", "1.0.0", &[("b", "^3")]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'a' requires 'b ^3', but no version matches"
+        );
+
+        let err = resolve_names(
+            repo.path(),
+            installed.path(),
+            package("a", "1.0.0", &[("b", "^1"), ("c", "*")]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No version of 'b' matches every requirement: 'a' requires '^1', 'c' requires '^2'"
+        );
+    }
+
+    #[test]
+    fn keeps_installed_dependents_working() {
+        let repo = tempfile::tempdir().unwrap();
+        let installed = tempfile::tempdir().unwrap();
+        publish(repo.path(), &package("b", "1.0.0", &[]));
+        publish(repo.path(), &package("b", "1.2.0", &[]));
+        publish(repo.path(), &package("b", "2.0.0", &[]));
+        testing::write_package(&installed.path().join("b"), &package("b", "1.0.0", &[]));
+        testing::write_package(
+            &installed.path().join("x"),
+            &package("x", "1.0.0", &[("b", "^1")]),
+        );
+
+        // The installed b is reused
+        let order = resolve_names(
+            repo.path(),
+            installed.path(),
+            package("a", "1.0.0", &[("b", "*")]),
+        )
+        .unwrap();
+        assert_eq!(order, vec!["b@1.0.0", "a@1.0.0"]);
+
+        // A newer b is chosen only if it still satisfies x
+        let order = resolve_names(
+            repo.path(),
+            installed.path(),
+            package("a", "1.0.0", &[("b", ">=1.1")]),
+        )
+        .unwrap();
+        assert_eq!(order, vec!["b@1.2.0", "a@1.0.0"]);
+
+        let err = resolve_names(
+            repo.path(),
+            installed.path(),
+            package("a", "1.0.0", &[("b", "^2")]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No version of 'b' matches every requirement: 'a' requires '^2', 'x' requires '^1'"
+        );
+
+        // Replacing x itself drops its old requirements
+        let order = resolve_names(
+            repo.path(),
+            installed.path(),
+            package("x", "2.0.0", &[("b", "^2")]),
+        )
+        .unwrap();
+        assert_eq!(order, vec!["b@2.0.0", "x@2.0.0"]);
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let repo = tempfile::tempdir().unwrap();
+        publish(repo.path(), &package("b", "1.0.0", &[("a", "*")]));
+
+        let available = LazyPackages {
+            dir: repo.path().to_path_buf(),
+        };
+        let err = resolve(
+            &available,
+            &available,
+            &Config::default(),
+            package("a", "1.0.0", &[("b", "*")]),
+            None,
+        )
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "Dependency cycle: a -> b -> a");
+    }
+}
@@ -0,0 +1,103 @@
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+use crate::{
+    package::Package,
+    packages::{self, Packages},
+    Config,
+};
+
+/// A package matching a search query.
+#[derive(Debug)]
+pub struct SearchResult {
+    /// Latest version of the package.
+    pub package: Package,
+    /// Remote the package comes from.
+    pub remote: Option<String>,
+    pub score: i64,
+    /// Installed version, if the package is installed.
+    pub installed: Option<String>,
+}
+
+/// Search the packages of every remote, best matches first.
+/// A package shipped by several remotes is listed once per remote.
+pub async fn search(config: &Config, query: &str) -> Result<Vec<SearchResult>, failure::Error> {
+    let installed = packages::installed_packages().lazy().await?;
+    let mut available = vec![];
+    for get_package in packages::remote_packages(config).load_latest().await? {
+        if let Ok(package) = get_package.package() {
+            available.push((package, get_package.remote));
+        }
+    }
+
+    Ok(rank(query, available)
+        .into_iter()
+        .map(|(package, remote, score)| SearchResult {
+            remote,
+            installed: installed
+                .get_package(&package.name)
+                .and_then(|x| x.package().ok())
+                .map(|x| x.version),
+            package,
+            score,
+        })
+        .collect())
+}
+
+/// Fuzzy match packages against a query, dropping those that don't match at all.
+/// Each package is returned with the data it was given alongside and its score.
+///
+/// Names count fully, descriptions and tags half, so a name match ranks first.
+pub fn rank<T>(query: &str, packages: Vec<(Package, T)>) -> Vec<(Package, T, i64)> {
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut ranked: Vec<(Package, T, i64)> = packages
+        .into_iter()
+        .filter_map(|(package, data)| {
+            let names = [&package.name, &package.friendly_name]
+                .into_iter()
+                .filter_map(|x| matcher.fuzzy_match(x, query));
+            let details = package
+                .description
+                .iter()
+                .chain(package.tags.iter())
+                .filter_map(|x| matcher.fuzzy_match(x, query))
+                .map(|x| x / 2);
+            let score = names.chain(details).max()?;
+            Some((package, data, score))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.name.cmp(&b.0.name)));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn package(name: &str, description: &str, tags: &[&str]) -> (Package, ()) {
+        let package = Package {
+            description: Some(description.to_string()),
+            tags: tags.iter().map(|x| x.to_string()).collect(),
+            ..testing::package(name, "1.0.0")
+        };
+        (package, ())
+    }
+
+    #[test]
+    fn name_matches_rank_first() {
+        let packages = vec![
+            package("ripgrep", "Search files with regexes", &["search"]),
+            package("fd", "Find files", &["search"]),
+            package("voran-search", "Search packages", &[]),
+            package("bat", "Print files", &["cat"]),
+        ];
+
+        let names: Vec<_> = rank("search", packages)
+            .into_iter()
+            .map(|(package, _, _)| package.name)
+            .collect();
+        assert_eq!(names[0], "voran-search");
+        assert!(!names.contains(&"bat".to_string()));
+        assert_eq!(names.len(), 3);
+    }
+}
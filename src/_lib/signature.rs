@@ -0,0 +1,138 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use failure::Fail;
+use minisign_verify::{PublicKey, Signature};
+
+/// Errors returned when a file's signature can't be verified.
+#[derive(Debug)]
+pub enum SignatureError {
+    /// The file has no `.minisig` signature next to it.
+    Missing { path: PathBuf },
+    /// A trusted key could not be parsed.
+    InvalidKey { key: String },
+    /// The signature file could not be read or parsed.
+    Invalid { path: PathBuf },
+    /// None of the trusted keys produced this signature.
+    Untrusted { path: PathBuf },
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::Missing { path } => {
+                write!(f, "'{}' is not signed", path.display())
+            }
+            SignatureError::InvalidKey { key } => {
+                write!(f, "'{}' is not a valid minisign public key", key)
+            }
+            SignatureError::Invalid { path } => {
+                write!(f, "Signature of '{}' is malformed", path.display())
+            }
+            SignatureError::Untrusted { path } => write!(
+                f,
+                "Signature of '{}' does not match any trusted key",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl Fail for SignatureError {}
+
+/// Path of the detached signature of a file (eg. package.toml -> package.toml.minisig).
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".minisig");
+    path.with_file_name(name)
+}
+
+/// Verify the detached minisign signature of a file against a list of base64 encoded public keys.
+pub fn verify_file(path: &Path, keys: &[String]) -> Result<(), SignatureError> {
+    let keys = keys
+        .iter()
+        .map(|key| {
+            PublicKey::from_base64(key).map_err(|_| SignatureError::InvalidKey { key: key.clone() })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let sig_path = signature_path(path);
+    if !sig_path.exists() {
+        return Err(SignatureError::Missing {
+            path: path.to_path_buf(),
+        });
+    }
+    let signature = Signature::from_file(&sig_path).map_err(|_| SignatureError::Invalid {
+        path: sig_path.clone(),
+    })?;
+    let contents = fs::read(path).map_err(|_| SignatureError::Invalid {
+        path: path.to_path_buf(),
+    })?;
+
+    if keys
+        .iter()
+        .any(|key| key.verify(&contents, &signature, false).is_ok())
+    {
+        Ok(())
+    } else {
+        Err(SignatureError::Untrusted {
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned_file_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.toml");
+        fs::write(&path, "name = \"my-package\"").unwrap();
+
+        assert_eq!(
+            signature_path(&path),
+            dir.path().join("package.toml.minisig")
+        );
+        let key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3".to_string();
+        match verify_file(&path, &[key]) {
+            Err(SignatureError::Missing { .. }) => {}
+            other => panic!("Expected a missing signature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn signed_file_is_verified_and_tampering_detected() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/signed");
+        let trusted = fs::read_to_string(fixtures.join("minisign.pub"))
+            .unwrap()
+            .lines()
+            .nth(1)
+            .unwrap()
+            .to_string();
+        let keys = [trusted];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("package.toml");
+        fs::copy(fixtures.join("package.toml"), &path).unwrap();
+        fs::copy(fixtures.join("package.toml.minisig"), signature_path(&path)).unwrap();
+
+        verify_file(&path, &keys).unwrap();
+
+        // Signed, but not by a trusted key
+        let other = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3".to_string();
+        match verify_file(&path, &[other]) {
+            Err(SignatureError::Untrusted { .. }) => {}
+            other => panic!("Expected an untrusted signature, got {:?}", other),
+        }
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, contents.replace("1.0.0", "1.0.1")).unwrap();
+        match verify_file(&path, &keys) {
+            Err(SignatureError::Untrusted { .. }) => {}
+            other => panic!("Expected an untrusted signature, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::package::{InstallInfo, Package, PackageType};
+
+/// A package with only the required fields set.
+pub fn package(name: &str, version: &str) -> Package {
+    Package {
+        schema: 1,
+        name: name.to_string(),
+        friendly_name: name.to_string(),
+        version: version.to_string(),
+        description: None,
+        tags: vec![],
+        homepage: None,
+        repository: None,
+        license: None,
+        authors: vec![],
+        min_voran_version: None,
+        install: InstallInfo {
+            url: format!("https://example.com/{}.jellyfish", name),
+            mirrors: vec![],
+            type_: PackageType::JellyFish,
+            format: None,
+            sha256: None,
+        },
+        dependencies: Default::default(),
+    }
+}
+
+/// Write a package to `<dir>/package.toml`, creating dir if needed.
+pub fn write_package(dir: &Path, package: &Package) -> PathBuf {
+    fs::create_dir_all(dir).unwrap();
+    let path = dir.join("package.toml");
+    fs::write(&path, toml::to_string(package).unwrap()).unwrap();
+    path
+}
@@ -0,0 +1,267 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    jellyfish_install::{self, JellyFishInstaller},
+    manifest::Manifest,
+    package::PackageType,
+    packages::GetPackage,
+};
+
+/// An install staged next to the package folder, committed with an atomic rename.
+///
+/// Until commit is called every change can be undone with rollback, restoring the previous
+/// install (if any) and the links it replaced.
+pub struct Transaction {
+    package_dir: PathBuf,
+    bin_dir: PathBuf,
+    /// Folder the new version is extracted into.
+    staging_dir: PathBuf,
+    /// Folder the previous version is moved to while the new one is activated.
+    backup_dir: PathBuf,
+    /// Folder replaced entries of the bin folder are moved to.
+    link_backup_dir: PathBuf,
+    /// (link, backup) pairs of replaced bin entries.
+    link_backups: Vec<(PathBuf, PathBuf)>,
+    created_links: Vec<PathBuf>,
+    /// Links of the previous version, removed on commit if the new version doesn't replace them.
+    old_links: Vec<PathBuf>,
+    has_backup: bool,
+    /// The previous version's Wharf script was reversed and must be rerun on rollback.
+    reversed_wharf: bool,
+    activated: bool,
+}
+
+impl Transaction {
+    /// Start a transaction for the package installed at package_dir.
+    /// Dot-prefixed folders are skipped when loading packages, so staging folders stay hidden.
+    pub fn begin(package_dir: PathBuf, bin_dir: PathBuf) -> Result<Self, failure::Error> {
+        let name = package_dir
+            .file_name()
+            .ok_or(failure::err_msg("Invalid package directory"))?
+            .to_string_lossy()
+            .to_string();
+        let transaction = Self {
+            staging_dir: package_dir.with_file_name(format!(".{}.new", name)),
+            backup_dir: package_dir.with_file_name(format!(".{}.old", name)),
+            link_backup_dir: bin_dir.join(format!(".{}.old", name)),
+            package_dir,
+            bin_dir,
+            link_backups: vec![],
+            created_links: vec![],
+            old_links: vec![],
+            has_backup: false,
+            reversed_wharf: false,
+            activated: false,
+        };
+
+        // Interrupted between the two renames of activate, the backup is the only copy of the
+        // previous install.
+        if !transaction.package_dir.exists() && transaction.backup_dir.exists() {
+            fs::rename(&transaction.backup_dir, &transaction.package_dir)?;
+        }
+        if transaction.link_backup_dir.exists() {
+            for entry in transaction.link_backup_dir.read_dir()? {
+                let entry = entry?;
+                let link = transaction.bin_dir.join(entry.file_name());
+                if link.symlink_metadata().is_err() {
+                    fs::rename(entry.path(), link)?;
+                }
+            }
+        }
+
+        // Leftovers of an interrupted transaction
+        for dir in [
+            &transaction.staging_dir,
+            &transaction.backup_dir,
+            &transaction.link_backup_dir,
+        ] {
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        Ok(transaction)
+    }
+
+    /// Folder the new version should be extracted into.
+    pub fn staging_dir(&self) -> &Path {
+        &self.staging_dir
+    }
+
+    /// Move the previous version out of the way and swap the staged one in.
+    pub fn activate(&mut self) -> Result<(), failure::Error> {
+        if self.package_dir.exists() {
+            let old = GetPackage {
+                dir: self.package_dir.clone(),
+                remote: None,
+            };
+            if let Ok(package) = old.package() {
+                if let PackageType::Wharf = package.install.type_ {
+                    wharf::reverse(self.package_dir.join("build.rope"));
+                    self.reversed_wharf = true;
+                }
+            }
+            if let Ok(manifest) = old.files() {
+                self.old_links = manifest.links;
+            }
+            fs::rename(&self.package_dir, &self.backup_dir)?;
+            self.has_backup = true;
+        }
+
+        fs::rename(&self.staging_dir, &self.package_dir)?;
+        self.activated = true;
+        Ok(())
+    }
+
+    /// Link the activated package's binaries, backing up any bin entries they replace.
+    pub fn link(
+        &mut self,
+        installer: &impl JellyFishInstaller,
+    ) -> Result<Vec<PathBuf>, failure::Error> {
+        let bin = self.package_dir.join("bin");
+        if bin.is_dir() {
+            for entry in bin.read_dir()? {
+                let link = self.bin_dir.join(entry?.file_name());
+                if link.symlink_metadata().is_ok() {
+                    fs::create_dir_all(&self.link_backup_dir)?;
+                    let backup = self.link_backup_dir.join(link.file_name().unwrap());
+                    fs::rename(&link, &backup)?;
+                    self.link_backups.push((link.clone(), backup));
+                }
+                // Anything at this path from now on was created by this install,
+                // even if linking fails halfway.
+                self.created_links.push(link);
+            }
+        }
+
+        let links = installer.link_to(self.package_dir.clone(), self.bin_dir.clone())?;
+        self.created_links = links.clone();
+
+        let mut manifest = Manifest::load(&self.package_dir)?;
+        manifest.links = links.clone();
+        manifest.save(&self.package_dir)?;
+        Ok(links)
+    }
+
+    /// Make the install permanent and drop the backups.
+    pub fn commit(self) -> Result<(), failure::Error> {
+        for link in &self.old_links {
+            let replaced = self.link_backups.iter().any(|(x, _)| x == link);
+            if !replaced && !self.created_links.contains(link) && link.symlink_metadata().is_ok() {
+                jellyfish_install::remove_link(link)?;
+            }
+        }
+        if self.link_backup_dir.exists() {
+            fs::remove_dir_all(&self.link_backup_dir)?;
+        }
+        if self.has_backup {
+            fs::remove_dir_all(&self.backup_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Undo every change, restoring the previous install exactly.
+    pub fn rollback(self) -> Result<(), failure::Error> {
+        for link in &self.created_links {
+            if link.symlink_metadata().is_ok() {
+                jellyfish_install::remove_link(link)?;
+            }
+        }
+        for (link, backup) in &self.link_backups {
+            fs::rename(backup, link)?;
+        }
+        if self.link_backup_dir.exists() {
+            fs::remove_dir_all(&self.link_backup_dir)?;
+        }
+
+        if self.activated {
+            fs::remove_dir_all(&self.package_dir)?;
+        }
+        if self.has_backup {
+            fs::rename(&self.backup_dir, &self.package_dir)?;
+            if self.reversed_wharf {
+                wharf::run(self.package_dir.join("build.rope"));
+            }
+        }
+        if self.staging_dir.exists() {
+            fs::remove_dir_all(&self.staging_dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jellyfish_install::MockJellyFishInstaller;
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_link_restores_previous_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("packages").join("tool");
+        let bin_dir = dir.path().join("bin");
+        fs::create_dir_all(package_dir.join("bin")).unwrap();
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(package_dir.join("bin/tool"), "old").unwrap();
+        std::os::unix::fs::symlink(package_dir.join("bin/tool"), bin_dir.join("tool")).unwrap();
+
+        let mut transaction = Transaction::begin(package_dir.clone(), bin_dir.clone()).unwrap();
+        let staging = transaction.staging_dir().to_path_buf();
+        fs::create_dir_all(staging.join("bin")).unwrap();
+        fs::write(staging.join("bin/tool"), "new").unwrap();
+        Manifest::scan(&staging).unwrap().save(&staging).unwrap();
+        transaction.activate().unwrap();
+
+        let mut installer = MockJellyFishInstaller::default();
+        installer.expect_link_to().returning(|out, bin_path| {
+            std::os::unix::fs::symlink(out.join("bin/tool"), bin_path.join("tool")).unwrap();
+            Err(failure::err_msg("Linking failed"))
+        });
+        assert!(transaction.link(&installer).is_err());
+        transaction.rollback().unwrap();
+
+        assert_eq!(fs::read_to_string(bin_dir.join("tool")).unwrap(), "old");
+        assert_eq!(
+            fs::read_link(bin_dir.join("tool")).unwrap(),
+            package_dir.join("bin/tool")
+        );
+        let mut leftovers: Vec<_> = dir
+            .path()
+            .join("packages")
+            .read_dir()
+            .unwrap()
+            .map(|x| x.unwrap().file_name())
+            .collect();
+        leftovers.extend(bin_dir.read_dir().unwrap().map(|x| x.unwrap().file_name()));
+        assert_eq!(leftovers, vec!["tool", "tool"]);
+    }
+
+    #[test]
+    fn interrupted_activate_restores_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let packages = dir.path().join("packages");
+        let bin_dir = dir.path().join("bin");
+        fs::create_dir_all(packages.join(".tool.old")).unwrap();
+        fs::create_dir_all(packages.join(".tool.new")).unwrap();
+        fs::create_dir_all(bin_dir.join(".tool.old")).unwrap();
+        fs::write(packages.join(".tool.old/tool"), "old").unwrap();
+        fs::write(bin_dir.join(".tool.old/tool"), "old link").unwrap();
+
+        Transaction::begin(packages.join("tool"), bin_dir.clone()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(packages.join("tool/tool")).unwrap(),
+            "old"
+        );
+        assert_eq!(
+            fs::read_to_string(bin_dir.join("tool")).unwrap(),
+            "old link"
+        );
+        assert!(!packages.join(".tool.old").exists());
+        assert!(!packages.join(".tool.new").exists());
+        assert!(!bin_dir.join(".tool.old").exists());
+    }
+}
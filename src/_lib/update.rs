@@ -1,14 +1,25 @@
-use std::{path::PathBuf, sync::Arc, thread, time::Duration};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc, thread, time::Duration};
 
+use semver::Version;
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use indicatif::ProgressBar;
 
-use crate::packages::{self, Packages};
+use crate::{
+    install,
+    packages::{self, GetPackage, Packages, RemotePackages},
+    resolve, Config,
+};
 
-pub async fn check_for_updates(pb: &ProgressBar) -> Result<Vec<Update>, failure::Error> {
+/// Find installed packages with a newer version available.
+/// Packages are checked against the remote they were installed from, if it is known.
+pub async fn check_for_updates(
+    config: &Config,
+    pb: &ProgressBar,
+) -> Result<Vec<Update>, failure::Error> {
     let installed: Vec<_> = packages::installed_packages().load().await?.collect();
+    let available = packages::remote_packages(config);
     pb.set_length(installed.len() as u64);
 
     let mut updates: Vec<Update> = vec![];
@@ -23,34 +34,15 @@ pub async fn check_for_updates(pb: &ProgressBar) -> Result<Vec<Update>, failure:
         });
         s.spawn(async {
             for pkg in installed {
-                let package = pkg
-                    .package()
-                    .ok_or(failure::err_msg("package.toml not found"))
-                    .expect("Failed to check package");
-                let tmp = packages::get_packages()
-                    .lazy()
-                    .await
-                    .expect("Failed to check package");
-                let mut tmp = tmp
-                    .get_package(&package.name)
-                    .ok_or(failure::err_msg("This package does not exist"))
-                    .expect("Failed to check package");
-                let new_pkg = tmp
-                    .version("LATEST")
-                    .ok_or(failure::err_msg(
-                        "This package does not have a LATEST release",
-                    ))
-                    .expect("Failed to check package");
-                let new_package = new_pkg
-                    .package()
-                    .ok_or(failure::err_msg("package.toml not found")) //?;
-                    .expect("Failed to check package");
-
-                if package.version != new_package.version {
-                    updates.push(Update {
-                        path_old: pkg.dir,
-                        path_new: new_pkg.dir.to_owned(),
-                    });
+                match find_update(&pkg, &available) {
+                    Ok(Some(update)) => updates.push(update),
+                    Ok(None) => {}
+                    // A removed remote or package shouldn't stop the others from being checked
+                    Err(err) => pb.println(format!(
+                        "Skipping {}: {}",
+                        pkg.dir.file_name().unwrap_or_default().to_string_lossy(),
+                        err
+                    )),
                 }
                 *index.lock().await += 1;
             }
@@ -61,21 +53,304 @@ pub async fn check_for_updates(pb: &ProgressBar) -> Result<Vec<Update>, failure:
     Ok(updates)
 }
 
-#[derive(Serialize, Deserialize)]
+/// Check an installed package against the remote it was installed from.
+fn find_update(
+    installed: &GetPackage,
+    available: &impl Packages,
+) -> Result<Option<Update>, failure::Error> {
+    let package = installed.package()?;
+    let remote = installed.files().ok().and_then(|x| x.remote);
+    let name = match &remote {
+        Some(remote) => format!("{}/{}", remote, package.name),
+        None => package.name.clone(),
+    };
+    let mut tmp = available
+        .get_package(&name)
+        .ok_or_else(|| failure::err_msg(format!("'{}' is no longer available", name)))?;
+    let new_pkg = tmp
+        .latest()
+        .ok_or_else(|| failure::err_msg(format!("'{}' does not have any releases", name)))?;
+    let new_package = new_pkg.package()?;
+
+    if !is_newer(&new_package.version, &package.version) {
+        return Ok(None);
+    }
+    Ok(Some(Update {
+        path_old: installed.dir.clone(),
+        path_new: new_pkg.dir.to_owned(),
+        remote: new_pkg.remote.clone(),
+    }))
+}
+
+/// Whether version is newer than installed. Versions that aren't semver are compared by equality.
+fn is_newer(version: &str, installed: &str) -> bool {
+    match (Version::parse(version), Version::parse(installed)) {
+        (Ok(version), Ok(installed)) => version > installed,
+        _ => version != installed,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Update {
     pub path_old: PathBuf,
     pub path_new: PathBuf,
+    /// Remote the new version comes from.
+    #[serde(default)]
+    pub remote: Option<String>,
 }
 
 impl Update {
-    pub async fn apply(&self) -> Result<(), failure::Error> {
-        self.reinstall().await?;
+    /// Whether this updates the package given as `name` or `remote/name`.
+    pub fn is_for(&self, name: &str) -> bool {
+        let (remote, name) = packages::split_name(name);
+        self.path_old.file_name().and_then(|x| x.to_str()) == Some(name)
+            && (remote.is_none() || remote == self.remote.as_deref())
+    }
+
+    /// Upgrade the installed package to the new version.
+    pub async fn apply(&self, config: &Config) -> Result<(), failure::Error> {
+        self.reinstall(config).await?;
         Ok(())
     }
 
-    pub async fn reinstall(&self) -> Result<(), failure::Error> {
-        let _ = self.path_new;
-        let _ = self.path_old;
+    /// Download the new version and swap it in for the installed one.
+    /// Dependencies the new version needs that aren't installed yet are installed first.
+    pub async fn reinstall(&self, config: &Config) -> Result<(), failure::Error> {
+        let package = GetPackage {
+            dir: self.path_new.clone(),
+            remote: self.remote.clone(),
+        }
+        .verified_package(&config.package_keys(self.remote.as_deref()))?;
+        if !self.path_old.exists() {
+            return Err(failure::err_msg("This package is not installed"));
+        }
+        let order = resolve::resolve(
+            &packages::remote_packages(config),
+            &packages::installed_packages().lazy().await?,
+            config,
+            package,
+            self.remote.clone(),
+        )?;
+        for resolved in order.into_iter().filter(|x| !x.installed) {
+            install::install(config, &resolved.package, resolved.remote.as_deref()).await?;
+        }
         Ok(())
     }
 }
+
+/// Version folders of every package of every remote at one point in time.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Snapshot {
+    /// (remote, package name) -> version folders.
+    pub packages: BTreeMap<(String, String), Vec<String>>,
+}
+
+impl Snapshot {
+    /// Record the packages currently in the configured remotes.
+    pub async fn take(config: &Config) -> Result<Self, failure::Error> {
+        Self::of(&packages::remote_packages(config)).await
+    }
+
+    /// Record the packages currently in a set of remotes.
+    pub async fn of(available: &RemotePackages) -> Result<Self, failure::Error> {
+        let mut packages = BTreeMap::new();
+        for get_package in available.load().await? {
+            let name = get_package
+                .dir
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let remote = get_package.remote.clone().unwrap_or_default();
+            packages.insert((remote, name), get_package.versions());
+        }
+        Ok(Self { packages })
+    }
+
+    /// What changed between this snapshot and a later one.
+    /// Installed packages are given as (remote they came from, name).
+    pub fn diff(&self, after: &Snapshot, installed: &[(Option<String>, String)]) -> IndexDiff {
+        let is_installed = |(remote, name): &(String, String)| {
+            // Packages installed before remotes were recorded match any remote
+            installed
+                .iter()
+                .any(|(x, y)| y == name && (x.is_none() || x.as_deref() == Some(remote.as_str())))
+        };
+        let change = |key: &(String, String), versions: Vec<String>| PackageChange {
+            remote: key.0.clone(),
+            name: key.1.clone(),
+            versions,
+            installed: is_installed(key),
+        };
+
+        let mut diff = IndexDiff::default();
+        for (key, versions) in &after.packages {
+            match self.packages.get(key) {
+                None => diff.added.push(change(key, versions.clone())),
+                Some(old) => {
+                    let new: Vec<String> = versions
+                        .iter()
+                        .filter(|x| !old.contains(x))
+                        .cloned()
+                        .collect();
+                    if !new.is_empty() {
+                        diff.updated.push(change(key, new));
+                    }
+                }
+            }
+        }
+        for (key, versions) in &self.packages {
+            if !after.packages.contains_key(key) {
+                diff.removed.push(change(key, versions.clone()));
+            }
+        }
+        diff
+    }
+}
+
+/// Changes to the package index, eg. from pulling the remotes.
+#[derive(Debug, Default, PartialEq)]
+pub struct IndexDiff {
+    /// Packages that weren't in the index before.
+    pub added: Vec<PackageChange>,
+    /// Packages that are no longer in the index.
+    pub removed: Vec<PackageChange>,
+    /// Packages with new versions.
+    pub updated: Vec<PackageChange>,
+}
+
+impl IndexDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+
+    /// Changes to packages that are installed.
+    pub fn installed(&self) -> impl Iterator<Item = &PackageChange> {
+        self.added
+            .iter()
+            .chain(&self.removed)
+            .chain(&self.updated)
+            .filter(|x| x.installed)
+    }
+}
+
+/// A package that was added, removed or got new versions.
+#[derive(Debug, PartialEq)]
+pub struct PackageChange {
+    pub remote: String,
+    pub name: String,
+    /// Versions of an added or removed package, or the new versions of an updated one.
+    pub versions: Vec<String>,
+    /// Whether the package is installed.
+    pub installed: bool,
+}
+
+/// Installed packages as (remote they came from, name), for [`Snapshot::diff`].
+pub async fn installed_names() -> Result<Vec<(Option<String>, String)>, failure::Error> {
+    let dir = packages::installed_packages();
+    Ok(dir
+        .load()
+        .await?
+        .map(|x| {
+            let name = x
+                .dir
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            (x.files().ok().and_then(|x| x.remote), name)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{manifest::Manifest, testing};
+
+    fn snapshot(packages: &[(&str, &str, &[&str])]) -> Snapshot {
+        Snapshot {
+            packages: packages
+                .iter()
+                .map(|(remote, name, versions)| {
+                    (
+                        (remote.to_string(), name.to_string()),
+                        versions.iter().map(|x| x.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn write_package(dir: &Path, version: &str) {
+        testing::write_package(dir, &testing::package("tool", version));
+    }
+
+    #[test]
+    fn updates_come_from_the_recorded_remote() {
+        let dir = tempfile::tempdir().unwrap();
+        let installed = dir.path().join("installed/tool");
+        write_package(&installed, "1.0.0");
+        Manifest {
+            remote: Some("main".to_string()),
+            ..Default::default()
+        }
+        .save(&installed)
+        .unwrap();
+        write_package(&dir.path().join("main/tool/1.1.0"), "1.1.0");
+        let installed = GetPackage {
+            dir: installed,
+            remote: None,
+        };
+
+        let available = RemotePackages {
+            remotes: vec![("main".to_string(), dir.path().join("main"))],
+        };
+        let update = find_update(&installed, &available).unwrap().unwrap();
+        assert_eq!(update.remote.as_deref(), Some("main"));
+        assert!(update.is_for("tool"));
+        assert!(update.is_for("main/tool"));
+        assert!(!update.is_for("extra/tool"));
+
+        // The remote was removed
+        let available = RemotePackages { remotes: vec![] };
+        assert_eq!(
+            find_update(&installed, &available).unwrap_err().to_string(),
+            "'main/tool' is no longer available"
+        );
+    }
+
+    #[test]
+    fn snapshots_are_diffed() {
+        let before = snapshot(&[
+            ("main", "tool", &["1.0.0"]),
+            ("main", "old", &["0.1.0"]),
+            ("extra", "tool", &["1.0.0"]),
+        ]);
+        let after = snapshot(&[
+            ("main", "tool", &["1.0.0", "1.1.0"]),
+            ("main", "new", &["0.1.0"]),
+            ("extra", "tool", &["1.0.0"]),
+        ]);
+        let diff = before.diff(&after, &[(Some("main".to_string()), "tool".to_string())]);
+
+        let names = |changes: &[PackageChange]| -> Vec<String> {
+            changes
+                .iter()
+                .map(|x| format!("{}/{} {}", x.remote, x.name, x.versions.join(",")))
+                .collect()
+        };
+        assert_eq!(names(&diff.added), vec!["main/new 0.1.0"]);
+        assert_eq!(names(&diff.removed), vec!["main/old 0.1.0"]);
+        assert_eq!(names(&diff.updated), vec!["main/tool 1.1.0"]);
+        assert_eq!(
+            diff.installed()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["tool"]
+        );
+        assert!(before.diff(&before, &[]).is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use std::process;
+
+use clap::Args;
+use voran::{info, Config};
+
+pub async fn info(config: &Config, args: InfoArgs) {
+    let info = info::info(config, &args.package)
+        .await
+        .unwrap_or_else(|err| {
+            println!("Error: {}", err);
+            process::exit(1);
+        });
+
+    println!("Id:                {}", info.name);
+    if let Some(package) = &info.latest {
+        println!("Name:              {}", package.friendly_name);
+        if let Some(description) = &package.description {
+            println!("Description:       {}", description);
+        }
+        println!("Latest version:    {}", package.version);
+        println!("Install type:      {:?}", package.install.type_);
+        println!("Url:               {}", package.install.url);
+        for mirror in &package.install.mirrors {
+            println!("Mirror:            {}", mirror);
+        }
+    }
+    println!(
+        "Remote:            {}",
+        info.remote.as_deref().unwrap_or("unknown")
+    );
+    println!("Versions:          {}", info.versions.join(", "));
+
+    match info.installed {
+        Some(installed) => {
+            println!("Installed version: {}", installed.package.version);
+            println!("Location:          {}", installed.location.display());
+            if let Some(remote) = &installed.remote {
+                println!("Installed from:    {}", remote);
+            }
+            for link in installed.links {
+                println!("Linked:            {}", link.display());
+            }
+        }
+        None => println!("Installed version: not installed"),
+    }
+}
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// Name of the package, optionally as <remote>/<name>
+    pub package: String,
+}
@@ -1,93 +1,68 @@
-use std::{fs, process};
+use std::process;
 
 use clap::Args;
-use reqwest::Client;
 use voran::{
-    jellyfish_install,
-    package::PackageType,
+    install,
     packages::{self, Packages},
-    proj_dirs,
+    resolve, Config,
 };
 
-pub async fn install(args: InstallArgs) {
+pub async fn install(config: &Config, args: InstallArgs) {
+    let available = packages::remote_packages(config);
+
     // Make sure the package exists
-    let package = packages::get_packages()
-        .lazy()
-        .await
-        .unwrap()
+    let mut get_package = available
         .get_package(&args.package)
-        .expect("This package does not exist")
-        .version(&args.version.unwrap_or("LATEST".to_string()))
+        .expect("This package does not exist");
+    let remote = get_package.remote.clone();
+    let package = get_package
+        .select_version(args.version.as_deref())
         .expect("This version does not exist")
-        .package()
-        .unwrap();
+        .verified_package(&config.package_keys(remote.as_deref()))
+        .expect("Failed to load package.toml");
 
-    // Download the file
-    let proj_dirs = proj_dirs();
-    let cache_dir = proj_dirs.cache_dir();
-    if !cache_dir.exists() {
-        fs::create_dir_all(cache_dir).unwrap();
-    }
-    let out_file = voran::download_file(
-        &Client::new(),
-        &package.install.url,
-        cache_dir.join(format!("{}.jellyfish", package.name)),
+    // Resolve every dependency before downloading anything
+    let order = resolve::resolve(
+        &available,
+        &packages::installed_packages().lazy().await.unwrap(),
+        config,
+        package,
+        remote,
     )
-    .await
-    .expect("Failed to download file");
-    println!("Download complete!, Installing...");
+    .unwrap_or_else(|err| {
+        println!("Error: {}", err);
+        process::exit(1);
+    });
 
-    match package.install.type_ {
-        PackageType::Executable => {
-            process::Command::new(out_file)
-                .spawn()
-                .expect("Failed to execute");
-        }
-        PackageType::JellyFish | PackageType::Wharf => {
-            let installer = jellyfish_install::BasicJellyFishInstaller::new(out_file);
-            jellyfish_install::install_to(&installer,
-                            proj_dirs.data_dir().join("packages").join(&package.name),
-                            proj_dirs.data_dir().join("bin"),
-                            true,
-                        )
-                        .expect(
-                            "Failed to install package. This may be caused by a corrupted package or a lack of sufficient privileges",
-                        );
+    for resolved in order.into_iter().filter(|x| !x.installed) {
+        let package = resolved.package;
+        let remote = resolved.remote.as_deref();
 
-            // Store package information with package for later use.
-            fs::write(
-                proj_dirs
-                    .data_dir()
-                    .join("packages")
-                    .join(&package.name)
-                    .join("package.toml"),
-                toml::to_string(&package).unwrap(),
-            )
-            .expect("Failed to store package information");
+        // Download the file
+        let downloaded = install::download(config, &package)
+            .await
+            .expect("Failed to download file");
+        println!("Download complete from {}!, Installing...", downloaded.url);
 
-            if let PackageType::Wharf = package.install.type_ {
-                wharf::run(
-                    proj_dirs
-                        .data_dir()
-                        .join("packages")
-                        .join(&package.name)
-                        .join("build.rope"),
-                );
-            }
+        let links = install::install_file(config, &package, remote, downloaded.path).expect(
+            "Failed to install package. This may be caused by a corrupted package or a lack of sufficient privileges",
+        );
+        for link in links {
+            println!("Linked {}", link.display());
         }
-    };
 
-    println!(
-        "Successfully installed {} v{}",
-        package.friendly_name, package.version
-    );
+        println!(
+            "Successfully installed {} v{}",
+            package.friendly_name, package.version
+        );
+    }
 }
 
 #[derive(Args)]
 pub struct InstallArgs {
-    /// Name of the package to be installed
+    /// Name of the package to be installed, optionally as <remote>/<name>
     package: String,
-    /// Optional version of the package
+    /// Optional version or version requirement (eg. '^1.2') of the package
     #[clap(short, long)]
     version: Option<String>,
 }
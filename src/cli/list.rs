@@ -4,9 +4,9 @@ use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
 
 use voran::packages::GetPackage;
-use voran::update;
+use voran::{update, Config};
 
-pub async fn list(args: ListArgs) {
+pub async fn list(config: &Config, args: ListArgs) {
     // List upgradeable packages
     if args.upgradeable {
         let pb = ProgressBar::new(0);
@@ -16,7 +16,7 @@ pub async fn list(args: ListArgs) {
                 .unwrap()
                 .progress_chars("#>-"),
         );
-        let updates = update::check_for_updates(&pb)
+        let updates = update::check_for_updates(config, &pb)
             .await
             .expect("Failed to check for updates");
         pb.finish_and_clear();
@@ -34,9 +34,11 @@ pub async fn list(args: ListArgs) {
             for update in updates {
                 let get_package = GetPackage {
                     dir: update.path_old,
+                    remote: None,
                 };
                 let new_get_package = GetPackage {
                     dir: update.path_new,
+                    remote: None,
                 };
                 let package = get_package.package().expect("Invalid package");
                 let new_package = new_get_package.package().expect("Invalid package");
@@ -61,56 +63,79 @@ pub async fn list(args: ListArgs) {
             .load()
             .await
             .expect("Failed to load packages");
-        println!("|{:30}|{:30}|{:10}|", "Name", "Id", "Version");
         println!(
-            "|{:30}|{:30}|{:10}|",
+            "|{:30}|{:30}|{:10}|{:15}|",
+            "Name", "Id", "Version", "Remote"
+        );
+        println!(
+            "|{:30}|{:30}|{:10}|{:15}|",
             "-".repeat(30),
             "-".repeat(30),
-            "-".repeat(10)
+            "-".repeat(10),
+            "-".repeat(15)
         );
-        for package in packages {
-            let package = package.package().unwrap();
+        for get_package in packages {
+            let package = match get_package.package() {
+                Ok(package) => package,
+                Err(err) => {
+                    println!("Error: {}", err);
+                    continue;
+                }
+            };
+            let remote = get_package.files().ok().and_then(|x| x.remote);
             println!(
-                "|{:30}|{:30}|{:10}|",
-                package.friendly_name, package.name, package.version
+                "|{:30}|{:30}|{:10}|{:15}|",
+                package.friendly_name,
+                package.name,
+                package.version,
+                remote.unwrap_or_default()
             );
         }
         println!(
-            "|{:30}|{:30}|{:10}|",
+            "|{:30}|{:30}|{:10}|{:15}|",
             "-".repeat(30),
             "-".repeat(30),
-            "-".repeat(10)
+            "-".repeat(10),
+            "-".repeat(15)
         );
     }
     // List remote packages
     else if args.remote || (!args.local && !args.remote && !args.upgradeable) {
-        let packages = voran::packages::get_packages()
-            .load()
+        let packages = voran::packages::remote_packages(config)
+            .load_latest()
             .await
             .expect("Failed to load packages");
-        println!("|{:30}|{:30}|{:10}|", "Name", "Id", "Version");
         println!(
-            "|{:30}|{:30}|{:10}|",
+            "|{:30}|{:30}|{:10}|{:15}|",
+            "Name", "Id", "Version", "Remote"
+        );
+        println!(
+            "|{:30}|{:30}|{:10}|{:15}|",
             "-".repeat(30),
             "-".repeat(30),
-            "-".repeat(10)
+            "-".repeat(10),
+            "-".repeat(15)
         );
-        for mut package in packages {
-            let package = package
-                .version("LATEST")
-                .expect("Failed to load package")
-                .package()
-                .unwrap();
+        for get_package in packages {
+            let remote = get_package.remote.clone().unwrap_or_default();
+            let package = match get_package.package() {
+                Ok(package) => package,
+                Err(err) => {
+                    println!("Error: {}", err);
+                    continue;
+                }
+            };
             println!(
-                "|{:30}|{:30}|{:10}|",
-                package.friendly_name, package.name, package.version
+                "|{:30}|{:30}|{:10}|{:15}|",
+                package.friendly_name, package.name, package.version, remote
             );
         }
         println!(
-            "|{:30}|{:30}|{:10}|",
+            "|{:30}|{:30}|{:10}|{:15}|",
             "-".repeat(30),
             "-".repeat(30),
-            "-".repeat(10)
+            "-".repeat(10),
+            "-".repeat(15)
         );
     }
 }
@@ -1,11 +1,23 @@
+mod info;
 mod install;
 mod list;
+mod pack;
+mod publish;
 mod remote;
+mod repo;
+mod search;
 mod uninstall;
 mod update;
+mod upgrade;
 
+pub use info::*;
 pub use install::*;
 pub use list::*;
+pub use pack::*;
+pub use publish::*;
 pub use remote::*;
+pub use repo::*;
+pub use search::*;
 pub use uninstall::*;
 pub use update::*;
+pub use upgrade::*;
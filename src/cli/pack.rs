@@ -0,0 +1,44 @@
+use std::{path::PathBuf, process};
+
+use clap::Args;
+use voran::pack::{self, PackOptions};
+
+pub async fn pack(args: PackArgs) {
+    let options = PackOptions {
+        friendly_name: args.friendly_name.unwrap_or_else(|| args.name.clone()),
+        name: args.name,
+        version: args.version,
+        url: args.url,
+    };
+    let packed = pack::pack(&args.dir, &args.out, &options).unwrap_or_else(|err| {
+        println!("Error: {}", err);
+        process::exit(1);
+    });
+
+    println!("Packed {}", packed.archive.display());
+    println!("Wrote {}", packed.package_toml.display());
+    if let Some(sha256) = &packed.package.install.sha256 {
+        println!("sha256: {}", sha256);
+    }
+}
+
+#[derive(Args)]
+pub struct PackArgs {
+    /// Folder to pack, binaries go in its bin folder
+    pub dir: PathBuf,
+    /// Id of the package
+    #[clap(long)]
+    pub name: String,
+    /// Display name of the package, defaults to the id
+    #[clap(long)]
+    pub friendly_name: Option<String>,
+    /// Version of the package
+    #[clap(long)]
+    pub version: String,
+    /// Url the archive will be published at, {name} and {version} are replaced
+    #[clap(long)]
+    pub url: String,
+    /// Folder to write the archive and package.toml to
+    #[clap(long, short, default_value = ".")]
+    pub out: PathBuf,
+}
@@ -0,0 +1,37 @@
+use std::{path::PathBuf, process};
+
+use clap::Args;
+use voran::publish;
+
+pub async fn publish(args: PublishArgs) {
+    let published = publish::publish(&args.package, &args.repo, args.force).unwrap_or_else(|err| {
+        println!("Error: {}", err);
+        process::exit(1);
+    });
+    if let Err(err) = publish::commit(&args.repo, &published) {
+        println!("Error: Failed to commit the package: {}", err);
+        process::exit(1);
+    }
+
+    println!(
+        "Published {} v{} to {}",
+        published.package.name,
+        published.package.version,
+        args.repo.join(&published.dir).display()
+    );
+    if published.latest {
+        println!("LATEST now points to v{}", published.package.version);
+    }
+}
+
+#[derive(Args)]
+pub struct PublishArgs {
+    /// package.toml to publish, its .minisig signature is copied along if there is one
+    pub package: PathBuf,
+    /// Local clone of the package repository
+    #[clap(long)]
+    pub repo: PathBuf,
+    /// Overwrite the version if it was already published
+    #[clap(long)]
+    pub force: bool,
+}
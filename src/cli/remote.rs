@@ -1,41 +1,97 @@
 use clap::{Args, Subcommand};
 
-use voran::{save_config, Config};
+use voran::{package::is_folder_name, packages::RemoteKind, save_config, Config};
 
 pub async fn remote(config: &mut Config, args: RemoteArgs) {
     let subcommand = args.subcommand.unwrap_or(RemoteCommand::List);
     match subcommand {
         RemoteCommand::Add(args) => {
+            if !is_folder_name(&args.name) {
+                println!(
+                    "Error: Remote names can't be empty, start with '.' or contain '/' or '\\'"
+                );
+                return;
+            }
             if config.git_repo_urls.iter().any(|f| f.0 == args.name) {
                 println!(
                     "Error: This remote already exists, remove it with `voran remote remove {}`",
                     args.name
                 );
+                return;
+            }
+            // Local folders are read in place, so they must not depend on the current folder.
+            let url = match RemoteKind::from_url(&args.url) {
+                RemoteKind::Local(path) => match path.canonicalize() {
+                    Ok(path) if path.is_dir() => format!("dir+{}", path.display()),
+                    _ => {
+                        println!("Error: '{}' is not a folder", path.display());
+                        return;
+                    }
+                },
+                _ => args.url,
+            };
+            for key in args.keys {
+                config.trusted_keys.push((args.name.clone(), key));
             }
-            config.git_repo_urls.push((args.name, args.url));
+            if let Some(reference) = args.reference {
+                config.remote_refs.push((args.name.clone(), reference));
+            }
+            config.git_repo_urls.push((args.name, url));
 
-            save_config(&config).expect("Failed to save configuration");
+            save_config(config).expect("Failed to save configuration");
         }
         RemoteCommand::Remove(args) => {
-            let config: Vec<(String, String)> = config
-                .git_repo_urls
+            config.git_repo_urls.retain(|x| x.0 != args.name);
+            config.trusted_keys.retain(|x| x.0 != args.name);
+            config.remote_refs.retain(|x| x.0 != args.name);
+            config.remote_priority.retain(|x| x != &args.name);
+
+            save_config(config).expect("Failed to save configuration");
+        }
+        RemoteCommand::Priority(args) => {
+            if let Some(name) = args
+                .remotes
                 .iter()
-                .filter(|x| x.0 != args.name)
-                .cloned()
-                .collect();
+                .find(|name| !config.git_repo_urls.iter().any(|x| &&x.0 == name))
+            {
+                println!("Error: Remote '{}' does not exist", name);
+                return;
+            }
+            config.remote_priority = args.remotes;
 
-            save_config(&Config {
-                git_repo_urls: config,
-            })
-            .expect("Failed to save configuration");
+            save_config(config).expect("Failed to save configuration");
         }
         RemoteCommand::List => {
-            println!("|{:30}|{:50}|", "Name", "Url");
-            println!("|{:30}|{:50}|", "-".repeat(30), "-".repeat(50));
-            for (name, url) in &config.git_repo_urls {
-                println!("|{:30}|{:50}|", name, url);
+            println!("|{:30}|{:50}|{:20}|{:6}|", "Name", "Url", "Ref", "Keys");
+            println!(
+                "|{:30}|{:50}|{:20}|{:6}|",
+                "-".repeat(30),
+                "-".repeat(50),
+                "-".repeat(20),
+                "-".repeat(6)
+            );
+            for name in config.remotes() {
+                let url = config
+                    .git_repo_urls
+                    .iter()
+                    .find(|x| x.0 == name)
+                    .map(|x| x.1.as_str())
+                    .unwrap_or_default();
+                println!(
+                    "|{:30}|{:50}|{:20}|{:6}|",
+                    name,
+                    url,
+                    config.remote_ref(&name).unwrap_or_default(),
+                    config.remote_keys(&name).len()
+                );
             }
-            println!("|{:30}|{:50}|", "-".repeat(30), "-".repeat(50));
+            println!(
+                "|{:30}|{:50}|{:20}|{:6}|",
+                "-".repeat(30),
+                "-".repeat(50),
+                "-".repeat(20),
+                "-".repeat(6)
+            );
         }
     }
 }
@@ -52,6 +108,9 @@ pub enum RemoteCommand {
     Add(RemoteAddArgs),
     /// Remove a remote
     Remove(RemoteRemoveArgs),
+    /// Set the order remotes are searched in for packages given without a remote
+    Priority(RemotePriorityArgs),
+    /// List remotes, highest priority first
     List,
 }
 
@@ -61,6 +120,12 @@ pub struct RemoteAddArgs {
     pub name: String,
     /// Url of the remote
     pub url: String,
+    /// Minisign public key (base64) that signs this remote's packages, can be given multiple times.
+    #[clap(long = "key")]
+    pub keys: Vec<String>,
+    /// Branch, tag or commit to pin the remote to, instead of following its default branch.
+    #[clap(long = "ref")]
+    pub reference: Option<String>,
 }
 
 #[derive(Args)]
@@ -68,3 +133,9 @@ pub struct RemoteRemoveArgs {
     /// Name of the remote to be removed.
     pub name: String,
 }
+
+#[derive(Args)]
+pub struct RemotePriorityArgs {
+    /// Remotes from highest to lowest priority, remotes not given follow in the order they were added.
+    pub remotes: Vec<String>,
+}
@@ -0,0 +1,85 @@
+use std::{fs, path::PathBuf, process};
+
+use clap::{Args, Subcommand};
+use voran::{index::Index, lint};
+
+pub async fn repo(args: RepoArgs) {
+    match args.subcommand {
+        RepoCommand::Lint(args) => lint(args).await,
+        RepoCommand::Index(args) => index(args).await,
+    }
+}
+
+async fn index(args: RepoIndexArgs) {
+    let index = Index::scan(&args.dir).await.unwrap_or_else(|err| {
+        println!("Error: {}", err);
+        process::exit(1);
+    });
+    let path = args.dir.join(voran::index::INDEX_FILE);
+    fs::write(&path, toml::to_string(&index).unwrap()).expect("Failed to write index");
+    println!(
+        "Wrote {} with {} packages",
+        path.display(),
+        index.packages.len()
+    );
+}
+
+async fn lint(args: RepoLintArgs) {
+    let reports = lint::lint(&args.dir, args.hashes)
+        .await
+        .unwrap_or_else(|err| {
+            println!("Error: {}", err);
+            process::exit(1);
+        });
+
+    let mut problems = 0;
+    for report in &reports {
+        if report.problems.is_empty() {
+            continue;
+        }
+        println!("{}:", report.name);
+        for problem in &report.problems {
+            println!("    {}", problem);
+        }
+        problems += report.problems.len();
+    }
+    println!(
+        "Checked {} packages, found {} problems",
+        reports.len(),
+        problems
+    );
+    if problems > 0 {
+        process::exit(1);
+    }
+}
+
+#[derive(Args)]
+pub struct RepoArgs {
+    #[clap(subcommand)]
+    pub subcommand: RepoCommand,
+}
+
+#[derive(Subcommand)]
+pub enum RepoCommand {
+    /// Check a package repository for mistakes before publishing it
+    Lint(RepoLintArgs),
+    /// Write the index.toml needed to serve a repository as an HTTP index remote
+    Index(RepoIndexArgs),
+}
+
+#[derive(Args)]
+pub struct RepoLintArgs {
+    /// Root of the package repository
+    #[clap(default_value = ".")]
+    pub dir: PathBuf,
+    /// Download every artifact and check it against its sha256
+    #[clap(long)]
+    pub hashes: bool,
+}
+
+#[derive(Args)]
+pub struct RepoIndexArgs {
+    /// Root of the package repository
+    #[clap(default_value = ".")]
+    pub dir: PathBuf,
+}
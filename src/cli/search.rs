@@ -0,0 +1,50 @@
+use clap::Args;
+use voran::{search, Config};
+
+pub async fn search(config: &Config, args: SearchArgs) {
+    let results = search::search(config, &args.query)
+        .await
+        .expect("Failed to search packages");
+
+    if results.is_empty() {
+        println!("No packages match '{}'", args.query);
+        return;
+    }
+
+    println!(
+        "|{:30}|{:30}|{:10}|{:15}|{:10}|",
+        "Name", "Id", "Version", "Remote", "Installed"
+    );
+    println!(
+        "|{:30}|{:30}|{:10}|{:15}|{:10}|",
+        "-".repeat(30),
+        "-".repeat(30),
+        "-".repeat(10),
+        "-".repeat(15),
+        "-".repeat(10)
+    );
+    for result in results {
+        println!(
+            "|{:30}|{:30}|{:10}|{:15}|{:10}|",
+            result.package.friendly_name,
+            result.package.name,
+            result.package.version,
+            result.remote.unwrap_or_default(),
+            result.installed.unwrap_or_default()
+        );
+    }
+    println!(
+        "|{:30}|{:30}|{:10}|{:15}|{:10}|",
+        "-".repeat(30),
+        "-".repeat(30),
+        "-".repeat(10),
+        "-".repeat(15),
+        "-".repeat(10)
+    );
+}
+
+#[derive(Args)]
+pub struct SearchArgs {
+    /// Text to search package names, descriptions and tags for
+    pub query: String,
+}
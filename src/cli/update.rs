@@ -1,68 +1,45 @@
-use std::{sync::Arc, thread, time::Duration};
+use std::time::Duration;
 
 use clap::Args;
 use indicatif::{ProgressBar, ProgressStyle};
-use tokio::sync::Mutex;
-use voran::{packages, update, Config};
+use voran::{
+    remotes::{self, SyncAction},
+    update::{self, IndexDiff},
+    Config,
+};
 
 pub async fn update(config: &Config, args: UpdateArgs) {
     if !args.no_pull {
-        let mut repository = packages::get_packages().git().await.unwrap();
+        let before = update::Snapshot::take(config)
+            .await
+            .expect("Failed to read package index");
 
-        let remotes = repository.remotes();
-        let remotes_len = remotes.len();
-
-        let index: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
-
-        let pb = ProgressBar::new(remotes_len.saturating_sub(1) as u64);
+        let pb = ProgressBar::new_spinner();
         pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner} [{bar:40.cyan/blue}] Setting up remotes...")
-                .unwrap()
-                .progress_chars("#>-"),
+            ProgressStyle::default_spinner()
+                .template("{spinner} Syncing remotes...")
+                .unwrap(),
         );
+        pb.enable_steady_tick(Duration::from_millis(100));
+        let results = remotes::sync(config).await.expect("Failed to sync remotes");
+        pb.finish_and_clear();
 
-        tokio_scoped::scope(|s| {
-            s.spawn(async {
-                while index.lock().await.saturating_add(0) < pb.length().unwrap() {
-                    pb.set_position(index.lock().await.saturating_add(0));
-                    thread::sleep(Duration::from_millis(100));
-                }
-                pb.finish_and_clear();
-            });
-            s.spawn(async {
-                for (i, remote) in remotes.iter().enumerate() {
-                    repository.remove_remote(&remote);
-                    *index.lock().await = i as u64;
-                }
-            });
-        });
-
-        let pb = ProgressBar::new(config.git_repo_urls.len().saturating_sub(1) as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner} [{bar:40.cyan/blue}] Pulling remotes...")
-                .unwrap()
-                .progress_chars("#>-"),
-        );
-        let index: Arc<Mutex<u64>> = Arc::new(Mutex::new(0));
-        tokio_scoped::scope(|s| {
-            s.spawn(async {
-                while index.lock().await.saturating_add(0) < pb.length().unwrap() {
-                    pb.set_position(index.lock().await.saturating_add(0));
-                    thread::sleep(Duration::from_millis(100));
-                }
-                pb.finish_and_clear();
-            });
-            s.spawn(async {
-                for (i, (name, url)) in config.git_repo_urls.iter().enumerate() {
-                    repository.add_remote(&name, &url);
+        for sync in results {
+            match sync.result {
+                Ok(SyncAction::Added) => println!("Added remote '{}'", sync.remote),
+                Ok(SyncAction::Updated) => println!("Updated remote '{}'", sync.remote),
+                Ok(SyncAction::Removed) => println!("Removed remote '{}'", sync.remote),
+                Err(err) => println!("Error: Failed to update remote '{}': {}", sync.remote, err),
+            }
+        }
 
-                    repository.pull(&name);
-                    *index.lock().await = i as u64;
-                }
-            });
-        });
+        let after = update::Snapshot::take(config)
+            .await
+            .expect("Failed to read package index");
+        let installed = update::installed_names()
+            .await
+            .expect("Failed to read installed packages");
+        print_diff(&before.diff(&after, &installed));
     }
 
     // Check for updates
@@ -73,7 +50,7 @@ pub async fn update(config: &Config, args: UpdateArgs) {
             .unwrap()
             .progress_chars("#>-"),
     );
-    let updates = update::check_for_updates(&pb)
+    let updates = update::check_for_updates(config, &pb)
         .await
         .expect("Failed to check for package updates");
     pb.finish();
@@ -83,6 +60,36 @@ pub async fn update(config: &Config, args: UpdateArgs) {
     println!("Update successful");
 }
 
+fn print_diff(diff: &IndexDiff) {
+    if diff.is_empty() {
+        println!("No changes to the package index");
+        return;
+    }
+    for (title, changes) in [
+        ("New packages", &diff.added),
+        ("Removed packages", &diff.removed),
+        ("New versions", &diff.updated),
+    ] {
+        if changes.is_empty() {
+            continue;
+        }
+        println!("{}:", title);
+        for change in changes {
+            println!(
+                "  {}/{} {}{}",
+                change.remote,
+                change.name,
+                change.versions.join(", "),
+                if change.installed { " (installed)" } else { "" }
+            );
+        }
+    }
+    let installed = diff.installed().count();
+    if installed > 0 {
+        println!("{} installed packages changed", installed);
+    }
+}
+
 #[derive(Args)]
 pub struct UpdateArgs {
     /// Check for package updates without pulling latest changes from remotes.
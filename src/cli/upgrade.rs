@@ -0,0 +1,66 @@
+use clap::Args;
+use indicatif::{ProgressBar, ProgressStyle};
+use voran::{packages::GetPackage, update, Config};
+
+pub async fn upgrade(config: &Config, args: UpgradeArgs) {
+    if !args.all && args.packages.is_empty() {
+        println!("Error: specify the packages to upgrade, or use --all");
+        return;
+    }
+
+    let pb = ProgressBar::new(0);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner} [{bar:40.cyan/blue}] Checking for new application versions...")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    let updates = update::check_for_updates(config, &pb)
+        .await
+        .expect("Failed to check for updates");
+    pb.finish_and_clear();
+
+    let mut upgraded = 0;
+    for update in updates {
+        let package = GetPackage {
+            dir: update.path_old.clone(),
+            remote: None,
+        }
+        .package()
+        .expect("Invalid package");
+        if !args.all && !args.packages.iter().any(|x| update.is_for(x)) {
+            continue;
+        }
+        let new_package = GetPackage {
+            dir: update.path_new.clone(),
+            remote: None,
+        }
+        .package()
+        .expect("Invalid package");
+
+        println!(
+            "Upgrading {} v{} -> v{}",
+            package.friendly_name, package.version, new_package.version
+        );
+        update
+            .apply(config)
+            .await
+            .expect("Failed to upgrade package");
+        upgraded += 1;
+    }
+
+    if upgraded == 0 {
+        println!("Woohoo! No packages to be upgraded!");
+    } else {
+        println!("Successfully upgraded {} packages", upgraded);
+    }
+}
+
+#[derive(Args)]
+pub struct UpgradeArgs {
+    /// Names of the packages to be upgraded, optionally as <remote>/<name>
+    pub packages: Vec<String>,
+    /// Upgrade every package with a newer version available
+    #[clap(short, long)]
+    pub all: bool,
+}
@@ -6,6 +6,10 @@ use serde::{Deserialize, Serialize};
 mod _lib;
 pub use _lib::*;
 
+use extract::ExtractLimits;
+use jellyfish_install::LinkMode;
+use package::InstallInfo;
+
 /// Load config saved on local disk.
 pub fn load_local_config() -> Config {
     let proj_dirs = proj_dirs();
@@ -42,9 +46,153 @@ pub fn save_config(config: &Config) -> Result<(), failure::Error> {
 #[derive(Deserialize, Serialize, Default)]
 pub struct Config {
     pub git_repo_urls: Vec<(String, String)>,
+    /// Minisign public keys (base64) trusted for each remote, as (remote name, key).
+    #[serde(default)]
+    pub trusted_keys: Vec<(String, String)>,
+    /// How package binaries are placed in the bin folder.
+    #[serde(default)]
+    pub link_mode: LinkMode,
+    /// Mirror rewrite rules as (url prefix, replacement), eg. to download through an artifact proxy.
+    #[serde(default)]
+    pub mirror_rewrites: Vec<(String, String)>,
+    /// Git refs (branch, tag or commit) remotes are pinned to, as (remote name, ref).
+    /// Remotes without one follow their default branch.
+    #[serde(default)]
+    pub remote_refs: Vec<(String, String)>,
+    /// Remotes searched first for packages given without a remote (eg. `ripgrep` rather than
+    /// `main/ripgrep`). Remotes not listed follow in the order they were added.
+    #[serde(default)]
+    pub remote_priority: Vec<String>,
+    /// Limits on how much a package may extract.
+    #[serde(default)]
+    pub extract_limits: ExtractLimits,
+}
+
+impl Config {
+    /// Public keys trusted for a remote.
+    pub fn remote_keys(&self, remote: &str) -> Vec<String> {
+        self.trusted_keys
+            .iter()
+            .filter(|(name, _)| name == remote)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+
+    /// Url of a remote.
+    pub fn remote_url(&self, remote: &str) -> Option<&str> {
+        self.git_repo_urls
+            .iter()
+            .find(|(name, _)| name == remote)
+            .map(|(_, url)| url.as_str())
+    }
+
+    /// Git ref a remote is pinned to, if any.
+    pub fn remote_ref(&self, remote: &str) -> Option<&str> {
+        self.remote_refs
+            .iter()
+            .find(|(name, _)| name == remote)
+            .map(|(_, reference)| reference.as_str())
+    }
+
+    /// Public keys trusted for a package manifest from a remote.
+    ///
+    /// Packages that don't come from a known remote are trusted if any remote's key signed them.
+    /// Empty if no keys are configured, in which case unsigned manifests are accepted.
+    pub fn package_keys(&self, remote: Option<&str>) -> Vec<String> {
+        match remote {
+            Some(remote) => self.remote_keys(remote),
+            None => self
+                .trusted_keys
+                .iter()
+                .map(|(_, key)| key.clone())
+                .collect(),
+        }
+    }
+
+    /// Names of the configured remotes, highest priority first.
+    pub fn remotes(&self) -> Vec<String> {
+        let mut remotes: Vec<String> = self
+            .remote_priority
+            .iter()
+            .filter(|name| self.git_repo_urls.iter().any(|(x, _)| &x == name))
+            .cloned()
+            .collect();
+        for (name, _) in &self.git_repo_urls {
+            if !remotes.contains(name) {
+                remotes.push(name.clone());
+            }
+        }
+        remotes
+    }
+
+    /// Urls to download a package from: rewritten urls first, then the package's own mirrors.
+    pub fn mirror_urls(&self, install: &InstallInfo) -> Vec<String> {
+        let urls = install.urls();
+        let mut out: Vec<String> = vec![];
+        for url in &urls {
+            for (prefix, replacement) in &self.mirror_rewrites {
+                if let Some(rest) = url.strip_prefix(prefix.as_str()) {
+                    out.push(format!("{}{}", replacement, rest));
+                }
+            }
+        }
+        for url in urls {
+            if !out.contains(&url) {
+                out.push(url);
+            }
+        }
+        out
+    }
 }
 
 /// Get directories::ProjectDirs of this application.
 pub fn proj_dirs() -> ProjectDirs {
     ProjectDirs::from("", "", "Voran").unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use package::PackageType;
+
+    #[test]
+    fn mirror_urls_prefers_rewrites() {
+        let config = Config {
+            mirror_rewrites: vec![(
+                "https://github.com/".to_string(),
+                "https://proxy.example.com/github/".to_string(),
+            )],
+            ..Default::default()
+        };
+        let install = InstallInfo {
+            url: "https://github.com/a/b/releases/download/1.0.0/b.jellyfish".to_string(),
+            mirrors: vec!["https://mirror.example.com/b.jellyfish".to_string()],
+            type_: PackageType::JellyFish,
+            format: None,
+            sha256: None,
+        };
+
+        assert_eq!(
+            config.mirror_urls(&install),
+            vec![
+                "https://proxy.example.com/github/a/b/releases/download/1.0.0/b.jellyfish",
+                "https://github.com/a/b/releases/download/1.0.0/b.jellyfish",
+                "https://mirror.example.com/b.jellyfish",
+            ]
+        );
+    }
+
+    #[test]
+    fn remotes_follow_priority() {
+        let config = Config {
+            git_repo_urls: ["main", "extra", "work"]
+                .iter()
+                .map(|x| (x.to_string(), format!("https://example.com/{}.git", x)))
+                .collect(),
+            remote_priority: vec!["work".to_string(), "removed".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(config.remotes(), vec!["work", "main", "extra"]);
+    }
+}
@@ -13,17 +13,35 @@ async fn main() {
             cli::update(&config, args).await;
         }
         Command::Install(args) => {
-            cli::install(args).await;
+            cli::install(&config, args).await;
         }
         Command::Uninstall(args) => {
             cli::uninstall(args).await;
         }
         Command::List(args) => {
-            cli::list(args).await;
+            cli::list(&config, args).await;
         }
         Command::Remote(args) => {
             cli::remote(&mut config, args).await;
         }
+        Command::Upgrade(args) => {
+            cli::upgrade(&config, args).await;
+        }
+        Command::Search(args) => {
+            cli::search(&config, args).await;
+        }
+        Command::Info(args) => {
+            cli::info(&config, args).await;
+        }
+        Command::Repo(args) => {
+            cli::repo(args).await;
+        }
+        Command::Pack(args) => {
+            cli::pack(args).await;
+        }
+        Command::Publish(args) => {
+            cli::publish(args).await;
+        }
     }
 }
 
@@ -45,4 +63,16 @@ enum Command {
     List(cli::ListArgs),
     /// Manage remotes
     Remote(cli::RemoteArgs),
+    /// Upgrade installed packages
+    Upgrade(cli::UpgradeArgs),
+    /// Search for packages
+    Search(cli::SearchArgs),
+    /// Show information about a package
+    Info(cli::InfoArgs),
+    /// Work on a package repository
+    Repo(cli::RepoArgs),
+    /// Build a .jellyfish archive and its package.toml
+    Pack(cli::PackArgs),
+    /// Publish a package.toml into a local clone of a package repository
+    Publish(cli::PublishArgs),
 }
@@ -12,13 +12,25 @@ async fn get_packages_works() {
     fs::create_dir("packages/my-package").await.unwrap();
     fs::create_dir("packages/my-package/0.1.0").await.unwrap();
     let res_package = Package {
+        schema: 1,
         name: "my-package".to_string(),
         friendly_name: "My Package".to_string(),
         version: "0.1.0".to_string(),
+        description: None,
+        tags: vec![],
+        homepage: None,
+        repository: None,
+        license: None,
+        authors: vec![],
+        min_voran_version: None,
         install: InstallInfo {
             url: "https://google.com/index.html".to_string(),
+            mirrors: vec![],
             type_: PackageType::JellyFish,
+            format: None,
+            sha256: None,
         },
+        dependencies: Default::default(),
     };
     fs::write(
         "packages/my-package/0.1.0/package.toml",
@@ -38,7 +50,7 @@ async fn get_packages_works() {
         panic!("This package should not exist");
     }
     let package = my_package.package();
-    if package.is_some() {
+    if package.is_ok() {
         panic!("There is no package.toml there");
     }
     my_package.version("0.1.0").unwrap();